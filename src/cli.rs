@@ -0,0 +1,335 @@
+use std::process::ExitCode;
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{TimeZone, Utc};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::{
+    config::{
+        profile::{Profile, ProfileManager},
+        tui::{TuiConfig, TuiConfigMode},
+    },
+    utils::logger::{LogLevel, Logger},
+};
+
+/// Terminal UI for mihomo, runs headless when a subcommand is given
+#[derive(Parser)]
+#[command(version)]
+pub struct Cli {
+    /// Print machine readable output
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Manage profiles
+    #[command(subcommand)]
+    Profile(ProfileCommand),
+
+    /// Manage proxy groups
+    #[command(subcommand)]
+    Proxy(ProxyCommand),
+
+    /// Set the proxy mode and re-apply the active profile
+    Mode { mode: ModeArg },
+
+    /// Manage connections
+    #[command(subcommand)]
+    Conn(ConnCommand),
+}
+
+#[derive(Subcommand)]
+pub enum ProfileCommand {
+    /// List profiles
+    List,
+
+    /// Update a remote profile, or all of them without a name
+    Update {
+        name: Option<String>,
+
+        /// Only update profiles whose update interval elapsed
+        #[arg(long, conflicts_with = "name")]
+        due: bool,
+    },
+
+    /// Activate a profile by name, `Fallback` for the builtin one
+    Activate { name: String },
+}
+
+#[derive(Subcommand)]
+pub enum ProxyCommand {
+    /// Select a node in a selector group
+    Select { group: String, node: String },
+}
+
+#[derive(Subcommand)]
+pub enum ConnCommand {
+    /// Close all connections
+    CloseAll,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ModeArg {
+    Rule,
+    Global,
+    Direct,
+}
+
+impl From<ModeArg> for TuiConfigMode {
+    fn from(mode: ModeArg) -> Self {
+        match mode {
+            ModeArg::Rule => Self::Rule,
+            ModeArg::Global => Self::Global,
+            ModeArg::Direct => Self::Direct,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ProfileEntry {
+    #[serde(flatten)]
+    profile: Profile,
+    builtin: bool,
+    active: bool,
+}
+
+pub async fn run(command: Command, json: bool) -> ExitCode {
+    let result = match command {
+        Command::Profile(ProfileCommand::List) => list_profiles(json),
+        Command::Profile(ProfileCommand::Update { name, due }) => {
+            update_profiles(name, due, json).await
+        }
+        Command::Profile(ProfileCommand::Activate { name }) => activate_profile(name, json).await,
+        Command::Proxy(ProxyCommand::Select { group, node }) => {
+            select_proxy(group, node, json).await
+        }
+        Command::Mode { mode } => set_mode(mode.into(), json).await,
+        Command::Conn(ConnCommand::CloseAll) => close_all_connections(json).await,
+    };
+
+    // Headless runs have no log pane, so surface what would have gone there
+    for (log_level, text) in Logger::get_instance().lock().unwrap().get_buffer() {
+        if matches!(log_level, LogLevel::Warn | LogLevel::Error) {
+            eprintln!("{}", text);
+        }
+    }
+
+    match result {
+        Ok(code) => code,
+        Err(err) => {
+            if json {
+                println!("{}", json!({ "error": format!("{:#}", err) }));
+            } else {
+                eprintln!("error: {:#}", err);
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn list_profiles(json: bool) -> Result<ExitCode> {
+    let active = TuiConfig::global().lock().unwrap().active_profile.clone();
+    let profiles = ProfileManager::get_all().lock().unwrap().clone();
+
+    if json {
+        // Fallback comes first like in the text output, `activate Fallback` accepts it
+        let mut entries = vec![json!({
+            "name": "Fallback",
+            "builtin": true,
+            "active": active.is_none(),
+        })];
+        for profile in profiles {
+            entries.push(serde_json::to_value(ProfileEntry {
+                active: active.as_ref() == Some(&profile.uuid),
+                builtin: false,
+                profile,
+            })?);
+        }
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let format_date = |timestamp: Option<u64>| {
+        timestamp
+            .and_then(|t| Utc.timestamp_opt(t as i64, 0).single())
+            .map(|t| t.format("%Y-%m-%d").to_string())
+            .unwrap_or("N/A".into())
+    };
+
+    let print_row =
+        |marker: &str, name: &str, kind: &str, updated: &str, used: &str, expired: &str| {
+            let row = format!(
+                "{} {:<32} {:<8} {:<12} {:<8} {}",
+                marker, name, kind, updated, used, expired
+            );
+            println!("{}", row.trim_end());
+        };
+
+    print_row(" ", "NAME", "TYPE", "UPDATED AT", "USED", "EXPIRED AT");
+    print_row(
+        if active.is_none() { "*" } else { " " },
+        "Fallback",
+        "builtin",
+        "N/A",
+        "N/A",
+        "N/A",
+    );
+    for p in profiles {
+        print_row(
+            if active.as_ref() == Some(&p.uuid) {
+                "*"
+            } else {
+                " "
+            },
+            &p.name,
+            if p.remote.is_some() {
+                "remote"
+            } else {
+                "local"
+            },
+            &if p.remote.is_some() {
+                format_date(p.updated_at)
+            } else {
+                "N/A".into()
+            },
+            &p.get_used_str().unwrap_or("N/A".into()),
+            &format_date(p.expired_at),
+        );
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+async fn update_profiles(name: Option<String>, due: bool, json: bool) -> Result<ExitCode> {
+    let results = match name {
+        None if due => ProfileManager::update_due().await?,
+        Some(name) => {
+            let profile = find_profile(&name)?;
+            if profile.remote.is_none() {
+                bail!("profile \"{}\" is local", profile.name);
+            }
+            let result = ProfileManager::update(&profile.uuid).await;
+            vec![(profile.name, result)]
+        }
+        None => ProfileManager::update_all().await?,
+    };
+
+    let failed = results.iter().filter(|(_, r)| r.is_err()).count();
+    if json {
+        let entries = results
+            .iter()
+            .map(|(name, result)| match result {
+                Ok(_) => json!({ "name": name, "updated": true }),
+                Err(err) => {
+                    json!({ "name": name, "updated": false, "error": format!("{:#}", err) })
+                }
+            })
+            .collect::<Vec<_>>();
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+    } else {
+        for (name, result) in &results {
+            match result {
+                Ok(_) => println!("Profile \"{}\" updated", name),
+                Err(err) => eprintln!("could not update profile \"{}\": {:#}", name, err),
+            }
+        }
+    }
+
+    // Failures are already reported per profile, only the exit status is left
+    Ok(if failed > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+async fn activate_profile(name: String, json: bool) -> Result<ExitCode> {
+    let name = if name == "Fallback" {
+        ProfileManager::active_fallback_profile()
+            .await
+            .with_context(|| "could not activate fallback profile")?;
+        name
+    } else {
+        let profile = find_profile(&name)?;
+        profile
+            .activate()
+            .await
+            .with_context(|| format!("could not activate profile \"{}\"", profile.name))?;
+        profile.name
+    };
+
+    if json {
+        println!("{}", json!({ "active": name }));
+    } else {
+        println!("Profile \"{}\" activated", name);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+async fn select_proxy(group: String, node: String, json: bool) -> Result<ExitCode> {
+    let api = TuiConfig::global().lock().unwrap().get_mihomo_api();
+    api.update_proxy(&group, &node)
+        .await
+        .with_context(|| format!("could not select \"{}\" in \"{}\"", node, group))?;
+
+    if json {
+        println!("{}", json!({ "group": group, "now": node }));
+    } else {
+        println!("Selected \"{}\" in \"{}\"", node, group);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+async fn set_mode(mode: TuiConfigMode, json: bool) -> Result<ExitCode> {
+    let config = {
+        let mut config = TuiConfig::global().lock().unwrap();
+        config.mode = mode;
+        config.clone()
+    };
+    config.flush().await?;
+
+    // Mode is baked into the generated config, so regenerate it
+    ProfileManager::reactivate()
+        .await
+        .with_context(|| "could not re-apply active profile")?;
+
+    if json {
+        println!("{}", json!({ "mode": mode.as_str() }));
+    } else {
+        println!("Mode set to {}", mode.as_str());
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+async fn close_all_connections(json: bool) -> Result<ExitCode> {
+    let api = TuiConfig::global().lock().unwrap().get_mihomo_api();
+    let closed = api.get_connections().await?.connections.len();
+    api.close_all_connections().await?;
+
+    if json {
+        println!("{}", json!({ "closed": closed }));
+    } else {
+        println!("Closed {} connections", closed);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn find_profile(name: &str) -> Result<Profile> {
+    ProfileManager::get_all()
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|p| p.name == name || p.uuid == name)
+        .cloned()
+        .ok_or(anyhow!("profile \"{}\" not found", name))
+}
@@ -0,0 +1,532 @@
+use std::{cell::RefCell, collections::BTreeMap, time::Instant};
+
+use anyhow::Result;
+use crossterm::event::{Event, KeyCode, KeyEventKind};
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Style, Stylize},
+    text::{Line, Text},
+    widgets::{Block, BorderType, Cell, Clear, Paragraph, Row, Table, TableState},
+    Frame,
+};
+
+use crate::{
+    app::App,
+    config::tui::TuiConfig,
+    task::{TaskEvent, TaskManager},
+    utils::{
+        api::models::{self, Connection},
+        format::{format_bytes, format_rate},
+        logger::Logger,
+    },
+};
+
+use super::{create_log, create_popup_area, input::Input, Component};
+
+const TICKS_PER_POLL: u64 = 5;
+const COLUMNS: [&str; 8] = [
+    "Host", "Process", "Chains", "Rule", "Upload", "Download", "UL Speed", "DL Speed",
+];
+
+pub struct Connections {
+    ticks: u64,
+    polling: Option<u64>,
+    actions: Vec<u64>,
+    connections: Vec<ConnectionRow>,
+    last_polled_at: Option<Instant>,
+    sort_column: usize,
+    sort_descending: bool,
+    filter: Input,
+    filtering: bool,
+    /// Waiting for the user to confirm closing every matching connection
+    confirming: bool,
+    table_state: RefCell<TableState>,
+}
+
+impl Component for Connections {
+    fn new() -> Self {
+        *App::get_instance().help_text.lock().unwrap() =
+            "[ESC]Quit  [UP/DOWN]Move cursor  [/]Filter  [S]Sort column  [O]Sort order  [D]Close  [C]Close all matching".into();
+
+        Self {
+            ticks: 0,
+            polling: None,
+            actions: Vec::new(),
+            connections: Vec::new(),
+            last_polled_at: None,
+            sort_column: 7,
+            sort_descending: true,
+            filter: Input::default(),
+            filtering: false,
+            confirming: false,
+            table_state: RefCell::new(TableState::new().with_selected(0)),
+        }
+    }
+
+    fn render(&self, area: &Rect, frame: &mut Frame) {
+        let [filter_area, table_area, log_area] = Layout::vertical(vec![
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Ratio(1, 4),
+        ])
+        .areas(*area);
+
+        frame.render_widget(self.create_filter(), filter_area);
+        frame.render_stateful_widget(
+            self.create_table(),
+            table_area,
+            &mut self.table_state.borrow_mut(),
+        );
+        frame.render_widget(create_log(log_area.height), log_area);
+
+        if self.confirming {
+            let popup_area = create_popup_area(area, 56, 5);
+            frame.render_widget(Clear, popup_area);
+            frame.render_widget(self.create_confirm_close(), popup_area);
+        }
+    }
+
+    async fn tick(&mut self) -> Result<()> {
+        if self.ticks.is_multiple_of(TICKS_PER_POLL) {
+            self.poll();
+        }
+        self.ticks += 1;
+
+        Ok(())
+    }
+
+    async fn handle_event(&mut self, ev: &Event) -> Result<()> {
+        let Event::Key(key) = ev else {
+            return Ok(());
+        };
+        if key.kind != KeyEventKind::Press {
+            return Ok(());
+        }
+
+        if self.filtering {
+            match key.code {
+                KeyCode::Enter => self.filtering = false,
+                KeyCode::Esc => {
+                    self.filter.clear();
+                    self.filtering = false;
+                }
+                _ => {
+                    if self.filter.handle_key(key) {
+                        self.table_state.borrow_mut().select(Some(0));
+                    }
+                }
+            }
+
+            return Ok(());
+        }
+
+        if self.confirming {
+            match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    self.confirming = false;
+                    self.close_filtered();
+                }
+                KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => self.confirming = false,
+                _ => (),
+            }
+
+            return Ok(());
+        }
+
+        match key.code {
+            KeyCode::Up => {
+                let mut state = self.table_state.borrow_mut();
+                let selected = state.selected().unwrap_or(0);
+
+                if selected > 0 {
+                    state.select(Some(selected - 1));
+                }
+            }
+            KeyCode::Down => {
+                let total = self.get_filtered().len();
+                let mut state = self.table_state.borrow_mut();
+                let selected = state.selected().unwrap_or(0);
+
+                if selected + 1 < total {
+                    state.select(Some(selected + 1));
+                }
+            }
+            KeyCode::Char('/') => self.filtering = true,
+            KeyCode::Char('s') | KeyCode::Char('S') => {
+                self.sort_column = (self.sort_column + 1) % COLUMNS.len();
+                self.sort();
+            }
+            KeyCode::Char('o') | KeyCode::Char('O') => {
+                self.sort_descending = !self.sort_descending;
+                self.sort();
+            }
+            KeyCode::Char('d') | KeyCode::Char('D') | KeyCode::Delete => self.close_selected(),
+            KeyCode::Char('c') | KeyCode::Char('C') => {
+                self.confirming = !self.get_filtered().is_empty();
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    async fn handle_task(&mut self, ev: &TaskEvent) -> Result<()> {
+        if self.polling == Some(ev.id) {
+            self.polling = None;
+
+            match ev.output::<models::Connections>() {
+                Ok(value) => self.apply(value),
+                Err(err) => Logger::get_instance()
+                    .lock()
+                    .unwrap()
+                    .error(format!("could not fetch connections: {:#}", err)),
+            }
+        } else if let Some(index) = self.actions.iter().position(|id| *id == ev.id) {
+            self.actions.remove(index);
+
+            if let Err(err) = &ev.result {
+                Logger::get_instance()
+                    .lock()
+                    .unwrap()
+                    .error(format!("{:#}", err));
+            }
+            self.poll();
+        }
+
+        Ok(())
+    }
+
+    fn is_editing(&self) -> bool {
+        self.filtering || self.confirming
+    }
+}
+
+impl Connections {
+    fn poll(&mut self) {
+        if self.polling.is_some() {
+            return;
+        }
+
+        self.polling = Some(
+            TaskManager::get_instance().spawn("Fetch connections", async {
+                let api = TuiConfig::global().lock().unwrap().get_mihomo_api();
+                Ok(api.get_connections().await?)
+            }),
+        );
+    }
+
+    fn apply(&mut self, value: &models::Connections) {
+        // Speeds are derived from the traffic delta since the last poll
+        let now = Instant::now();
+        let elapsed = self
+            .last_polled_at
+            .map(|t| now.duration_since(t).as_secs_f64())
+            .unwrap_or(0.0);
+        let previous = self
+            .connections
+            .iter()
+            .map(|c| (c.id.clone(), (c.upload, c.download)))
+            .collect::<BTreeMap<String, (u64, u64)>>();
+
+        self.connections = value
+            .connections
+            .iter()
+            .map(ConnectionRow::from_connection)
+            .collect();
+        for connection in self.connections.iter_mut() {
+            if let Some((upload, download)) = previous.get(&connection.id) {
+                if elapsed > 0.0 {
+                    connection.upload_speed =
+                        (connection.upload.saturating_sub(*upload) as f64 / elapsed) as u64;
+                    connection.download_speed =
+                        (connection.download.saturating_sub(*download) as f64 / elapsed) as u64;
+                }
+            }
+        }
+        self.last_polled_at = Some(now);
+        self.sort();
+
+        // Keep the cursor inside the new bounds
+        let total = self.get_filtered().len();
+        let mut state = self.table_state.borrow_mut();
+        if state.selected().unwrap_or(0) >= total {
+            state.select(Some(total.saturating_sub(1)));
+        }
+    }
+
+    fn sort(&mut self) {
+        let column = self.sort_column;
+        let descending = self.sort_descending;
+
+        self.connections.sort_by(|a, b| {
+            let ordering = match column {
+                0 => a.host.cmp(&b.host),
+                1 => a.process.cmp(&b.process),
+                2 => a.chains.cmp(&b.chains),
+                3 => a.rule.cmp(&b.rule),
+                4 => a.upload.cmp(&b.upload),
+                5 => a.download.cmp(&b.download),
+                6 => a.upload_speed.cmp(&b.upload_speed),
+                _ => a.download_speed.cmp(&b.download_speed),
+            };
+
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
+
+    fn close_selected(&mut self) {
+        let selected = self.table_state.borrow().selected().unwrap_or(0);
+        let Some(connection) = self.get_filtered().get(selected).map(|c| (*c).clone()) else {
+            return;
+        };
+
+        self.actions.push(TaskManager::get_instance().spawn(
+            format!("Close connection to \"{}\"", connection.host),
+            async move {
+                let api = TuiConfig::global().lock().unwrap().get_mihomo_api();
+                api.close_connection(&connection.id).await?;
+
+                Logger::get_instance()
+                    .lock()
+                    .unwrap()
+                    .info(format!("Closed connection to \"{}\"", connection.host));
+
+                Ok(())
+            },
+        ));
+    }
+
+    fn close_filtered(&mut self) {
+        if self.filter.value().is_empty() {
+            self.actions.push(
+                TaskManager::get_instance().spawn("Close all connections", async {
+                    let api = TuiConfig::global().lock().unwrap().get_mihomo_api();
+                    api.close_all_connections().await?;
+
+                    Logger::get_instance()
+                        .lock()
+                        .unwrap()
+                        .info("Closed all connections");
+
+                    Ok(())
+                }),
+            );
+            return;
+        }
+
+        let keyword = self.filter.value().to_owned();
+        let ids = self
+            .get_filtered()
+            .into_iter()
+            .map(|c| c.id.clone())
+            .collect::<Vec<String>>();
+
+        self.actions.push(TaskManager::get_instance().spawn(
+            format!("Close connections matching \"{}\"", keyword),
+            async move {
+                let api = TuiConfig::global().lock().unwrap().get_mihomo_api();
+                let mut closed = 0;
+
+                for id in ids.iter() {
+                    match api.close_connection(id).await {
+                        Ok(_) => closed += 1,
+                        Err(err) => Logger::get_instance()
+                            .lock()
+                            .unwrap()
+                            .error(format!("{:#}", err)),
+                    }
+                }
+
+                Logger::get_instance().lock().unwrap().info(format!(
+                    "Closed {} connection(s) matching \"{}\"",
+                    closed, keyword
+                ));
+
+                Ok(())
+            },
+        ));
+    }
+
+    fn create_confirm_close(&self) -> Paragraph<'static> {
+        let total = self.get_filtered().len();
+        let question = if self.filter.value().is_empty() {
+            format!(" Close all {} connection(s)?", total)
+        } else {
+            format!(
+                " Close {} connection(s) matching \"{}\"?",
+                total,
+                self.filter.value()
+            )
+        };
+
+        Paragraph::new(vec![
+            Line::from(question),
+            Line::default(),
+            Line::from(" [Y]Yes  [N]No").dark_gray().italic(),
+        ])
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Double)
+                .title(" Close Connections ")
+                .border_style(Style::default().light_red()),
+        )
+    }
+
+    fn get_filtered(&self) -> Vec<&ConnectionRow> {
+        let keyword = self.filter.value().to_lowercase();
+
+        self.connections
+            .iter()
+            .filter(|c| {
+                keyword.is_empty()
+                    || [&c.host, &c.process, &c.chains, &c.rule]
+                        .iter()
+                        .any(|v| v.to_lowercase().contains(&keyword))
+            })
+            .collect()
+    }
+
+    fn create_filter(&self) -> Paragraph<'_> {
+        let block = Block::bordered()
+            .border_type(BorderType::Double)
+            .title(" Filter ");
+
+        if self.filtering {
+            Paragraph::new(self.filter.create_line(true))
+                .block(block.border_style(Style::default().light_yellow()))
+        } else if self.filter.value().is_empty() {
+            Paragraph::new("Press [/] to filter connections")
+                .dark_gray()
+                .italic()
+                .block(block)
+        } else {
+            Paragraph::new(self.filter.create_line(false)).block(block)
+        }
+    }
+
+    fn create_table(&self) -> Table<'_> {
+        let header = Row::new(
+            COLUMNS
+                .into_iter()
+                .enumerate()
+                .map(|(i, s)| {
+                    if i == self.sort_column {
+                        let arrow = if self.sort_descending { "▼" } else { "▲" };
+                        Cell::new(Text::from(format!("{} {}", s, arrow)).centered())
+                            .on_blue()
+                            .light_yellow()
+                    } else {
+                        Cell::new(Text::from(s).centered()).on_blue()
+                    }
+                })
+                .collect::<Vec<Cell>>(),
+        )
+        .on_light_blue()
+        .white()
+        .bold();
+
+        let filtered = self.get_filtered();
+        let title = format!(
+            " Connections ({}/{}) ",
+            filtered.len(),
+            self.connections.len()
+        );
+        let rows = filtered
+            .into_iter()
+            .map(|c| {
+                Row::new(vec![
+                    Cell::new(c.host.clone()),
+                    Cell::new(c.process.clone()),
+                    Cell::new(c.chains.clone()).light_green(),
+                    Cell::new(c.rule.clone()).light_yellow(),
+                    Cell::new(Text::from(format_bytes(c.upload)).right_aligned()),
+                    Cell::new(Text::from(format_bytes(c.download)).right_aligned()),
+                    Cell::new(Text::from(format_rate(c.upload_speed)).right_aligned()).light_cyan(),
+                    Cell::new(Text::from(format_rate(c.download_speed)).right_aligned())
+                        .light_green(),
+                ])
+            })
+            .collect::<Vec<Row>>();
+
+        Table::new(
+            rows,
+            vec![
+                Constraint::Min(16),
+                Constraint::Length(16),
+                Constraint::Min(16),
+                Constraint::Length(16),
+                Constraint::Length(12),
+                Constraint::Length(12),
+                Constraint::Length(14),
+                Constraint::Length(14),
+            ],
+        )
+        .header(header)
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Double)
+                .title(title),
+        )
+        .row_highlight_style(Style::default().on_white().black())
+    }
+}
+
+#[derive(Clone)]
+struct ConnectionRow {
+    id: String,
+    host: String,
+    process: String,
+    chains: String,
+    rule: String,
+    upload: u64,
+    download: u64,
+    upload_speed: u64,
+    download_speed: u64,
+}
+
+impl ConnectionRow {
+    fn from_connection(connection: &Connection) -> Self {
+        let metadata = &connection.metadata;
+
+        let host = match metadata.host.as_str() {
+            "" => format!("{}:{}", metadata.destination_ip, metadata.destination_port),
+            host => format!("{}:{}", host, metadata.destination_port),
+        };
+        let process = match metadata.process.as_str() {
+            "" => metadata
+                .process_path
+                .rsplit(['/', '\\'])
+                .next()
+                .unwrap_or("")
+                .to_owned(),
+            process => process.to_owned(),
+        };
+        let chains = connection
+            .chains
+            .iter()
+            .rev()
+            .map(|v| v.as_str())
+            .collect::<Vec<&str>>()
+            .join(" -> ");
+        let rule = match connection.rule_payload.as_str() {
+            "" => connection.rule.clone(),
+            payload => format!("{}({})", connection.rule, payload),
+        };
+
+        Self {
+            id: connection.id.clone(),
+            host,
+            process,
+            chains,
+            rule,
+            upload: connection.upload,
+            download: connection.download,
+            upload_speed: 0,
+            download_speed: 0,
+        }
+    }
+}
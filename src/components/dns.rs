@@ -0,0 +1,451 @@
+use std::{cell::RefCell, net::IpAddr, path::PathBuf};
+
+use anyhow::{Context, Result};
+use crossterm::event::{Event, KeyCode, KeyEventKind};
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Style, Stylize},
+    text::{Line, Span, Text},
+    widgets::{Block, BorderType, Cell, Paragraph, Row, Table, TableState},
+    Frame,
+};
+use serde_yaml::Value;
+
+use crate::{
+    app::App,
+    config::tui::TuiConfig,
+    task::{TaskEvent, TaskManager},
+    utils::{
+        api::models::{DnsAnswer, DnsResponse},
+        logger::Logger,
+        path::get_data_dir,
+    },
+};
+
+use super::{create_log, input::Input, Component};
+
+const RECORD_TYPES: [&str; 8] = ["A", "AAAA", "CNAME", "MX", "TXT", "NS", "SRV", "HTTPS"];
+const DEFAULT_FAKE_IP_RANGE: &str = "198.18.0.1/16";
+const PPROF_PROFILES: [&str; 2] = ["heap", "goroutine"];
+
+pub struct Dns {
+    hostname: Input,
+    editing: bool,
+    record_type: usize,
+    querying: Option<u64>,
+    actions: Vec<u64>,
+    result: Option<DnsLookup>,
+    answer_state: RefCell<TableState>,
+}
+
+impl Component for Dns {
+    fn new() -> Self {
+        *App::get_instance().help_text.lock().unwrap() =
+            "[ESC]Quit  [UP/DOWN]Move cursor  [E]Edit hostname  [T]Record type  [ENTER]Query  [F]Flush fake-ip cache  [P]Save pprof".into();
+
+        Self {
+            hostname: Input::default(),
+            editing: false,
+            record_type: 0,
+            querying: None,
+            actions: Vec::new(),
+            result: None,
+            answer_state: RefCell::new(TableState::new().with_selected(0)),
+        }
+    }
+
+    fn render(&self, area: &Rect, frame: &mut Frame) {
+        let [query_area, answer_area, log_area] = Layout::vertical(vec![
+            Constraint::Length(4),
+            Constraint::Min(0),
+            Constraint::Ratio(1, 4),
+        ])
+        .areas(*area);
+
+        frame.render_widget(self.create_query(), query_area);
+        frame.render_stateful_widget(
+            self.create_answer_table(),
+            answer_area,
+            &mut self.answer_state.borrow_mut(),
+        );
+        frame.render_widget(create_log(log_area.height), log_area);
+    }
+
+    async fn tick(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn handle_event(&mut self, ev: &Event) -> Result<()> {
+        let Event::Key(key) = ev else {
+            return Ok(());
+        };
+        if key.kind != KeyEventKind::Press {
+            return Ok(());
+        }
+
+        if self.editing {
+            match key.code {
+                KeyCode::Enter => {
+                    self.editing = false;
+                    self.query();
+                }
+                KeyCode::Esc => self.editing = false,
+                _ => {
+                    self.hostname.handle_key(key);
+                }
+            }
+
+            return Ok(());
+        }
+
+        match key.code {
+            KeyCode::Up => self.move_cursor(false),
+            KeyCode::Down => self.move_cursor(true),
+            KeyCode::Enter => self.query(),
+            KeyCode::Char('e') | KeyCode::Char('E') | KeyCode::Char('/') => self.editing = true,
+            KeyCode::Char('t') | KeyCode::Char('T') => {
+                self.record_type = (self.record_type + 1) % RECORD_TYPES.len()
+            }
+            KeyCode::Char('f') | KeyCode::Char('F') => self.flush_fake_ip_cache(),
+            KeyCode::Char('p') | KeyCode::Char('P') => self.save_pprof(),
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    async fn handle_task(&mut self, ev: &TaskEvent) -> Result<()> {
+        if self.querying == Some(ev.id) {
+            self.querying = None;
+
+            match ev.output::<DnsLookup>() {
+                Ok(result) => {
+                    self.result = Some(result.clone());
+                    self.answer_state.borrow_mut().select(Some(0));
+                }
+                Err(err) => Logger::get_instance()
+                    .lock()
+                    .unwrap()
+                    .error(format!("{:#}", err)),
+            }
+        } else if let Some(index) = self.actions.iter().position(|id| *id == ev.id) {
+            self.actions.remove(index);
+
+            if let Err(err) = &ev.result {
+                Logger::get_instance()
+                    .lock()
+                    .unwrap()
+                    .error(format!("{:#}", err));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_editing(&self) -> bool {
+        self.editing
+    }
+}
+
+impl Dns {
+    fn query(&mut self) {
+        let hostname = self.hostname.value().trim().to_owned();
+        if hostname.is_empty() || self.querying.is_some() {
+            return;
+        }
+        let record_type = RECORD_TYPES[self.record_type];
+
+        self.querying = Some(TaskManager::get_instance().spawn(
+            format!("Query {} \"{}\"", record_type, hostname),
+            async move {
+                let (api, data_dir) = {
+                    let config = TuiConfig::global().lock().unwrap();
+                    (
+                        config.get_mihomo_api(),
+                        config.controller().data_dir.clone(),
+                    )
+                };
+                let response = api
+                    .query_dns(&hostname, record_type)
+                    .await
+                    .with_context(|| format!("could not resolve \"{}\"", hostname))?;
+
+                Ok(DnsLookup {
+                    hostname,
+                    record_type,
+                    response,
+                    fake_ip_range: read_fake_ip_range(data_dir).await,
+                })
+            },
+        ));
+    }
+
+    fn flush_fake_ip_cache(&mut self) {
+        self.actions.push(
+            TaskManager::get_instance().spawn("Flush fake-ip cache", async {
+                let api = TuiConfig::global().lock().unwrap().get_mihomo_api();
+                api.clear_fake_ip_cache()
+                    .await
+                    .with_context(|| "could not flush fake-ip cache")?;
+
+                Logger::get_instance()
+                    .lock()
+                    .unwrap()
+                    .info("Fake-ip cache flushed");
+
+                Ok(())
+            }),
+        );
+    }
+
+    fn save_pprof(&mut self) {
+        self.actions.push(
+            TaskManager::get_instance().spawn("Save pprof profiles", async {
+                let api = TuiConfig::global().lock().unwrap().get_mihomo_api();
+                let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+
+                for profile in PPROF_PROFILES {
+                    let dump = api.get_pprof(profile).await.with_context(|| {
+                        format!("could not fetch {} profile, is log-level debug?", profile)
+                    })?;
+
+                    let path = get_data_dir().join(format!("pprof-{}-{}.txt", profile, timestamp));
+                    tokio::fs::write(&path, dump)
+                        .await
+                        .with_context(|| format!("could not write file `{}`", path.display()))?;
+
+                    Logger::get_instance().lock().unwrap().info(format!(
+                        "Saved {} profile to {}",
+                        profile,
+                        path.display()
+                    ));
+                }
+
+                Ok(())
+            }),
+        );
+    }
+
+    fn move_cursor(&mut self, down: bool) {
+        let total = self.result.as_ref().map(|r| r.records().len()).unwrap_or(0);
+        let mut state = self.answer_state.borrow_mut();
+        let selected = state.selected().unwrap_or(0);
+
+        if down && selected + 1 < total {
+            state.select(Some(selected + 1));
+        } else if !down && selected > 0 {
+            state.select(Some(selected - 1));
+        }
+    }
+
+    fn create_query(&self) -> Paragraph<'_> {
+        let label = |s: &'static str| Span::from(format!(" {:<10}", s)).bold();
+
+        let hostname = if self.editing {
+            self.hostname.create_line(true)
+        } else if self.hostname.value().is_empty() {
+            Line::from("Press [E] to enter a hostname")
+                .dark_gray()
+                .italic()
+        } else {
+            self.hostname.create_line(false)
+        };
+        let mut hostname_spans = vec![label("Hostname")];
+        hostname_spans.extend(
+            hostname
+                .spans
+                .into_iter()
+                .map(|s| s.patch_style(hostname.style)),
+        );
+
+        let lines = vec![
+            Line::from(hostname_spans),
+            Line::from(vec![
+                label("Type"),
+                Span::from(RECORD_TYPES[self.record_type]).light_yellow(),
+            ]),
+        ];
+
+        let block = Block::bordered()
+            .border_type(BorderType::Double)
+            .title(" DNS Query ");
+        if self.editing {
+            Paragraph::new(lines).block(block.border_style(Style::default().light_yellow()))
+        } else {
+            Paragraph::new(lines).block(block)
+        }
+    }
+
+    fn create_answer_table(&self) -> Table<'_> {
+        let header = Row::new(
+            ["Section", "Name", "Type", "TTL", "Data", ""]
+                .into_iter()
+                .map(|s| Cell::new(Text::from(s).centered()).on_blue())
+                .collect::<Vec<Cell>>(),
+        )
+        .on_light_blue()
+        .white()
+        .bold();
+
+        let rows = self
+            .result
+            .as_ref()
+            .map(|result| {
+                result
+                    .records()
+                    .into_iter()
+                    .map(|(section, record)| {
+                        Row::new(vec![
+                            Cell::new(section).dark_gray(),
+                            Cell::new(record.name.clone()),
+                            Cell::new(Text::from(record_type_name(record.record_type)).centered())
+                                .light_yellow(),
+                            Cell::new(Text::from(record.ttl.to_string()).right_aligned()),
+                            Cell::new(record.data.clone()).light_green(),
+                            if result.is_fake_ip(record) {
+                                Cell::new(Text::from("fake-ip").centered()).light_magenta()
+                            } else {
+                                Cell::default()
+                            },
+                        ])
+                    })
+                    .collect::<Vec<Row>>()
+            })
+            .unwrap_or_default();
+
+        let title = match &self.result {
+            Some(result) => format!(
+                " {} {} - {}{} ",
+                result.record_type,
+                result.hostname,
+                rcode_name(result.response.status),
+                if result.response.truncated {
+                    ", truncated"
+                } else {
+                    ""
+                }
+            ),
+            None => " Answers ".into(),
+        };
+
+        Table::new(
+            rows,
+            vec![
+                Constraint::Length(10),
+                Constraint::Min(4),
+                Constraint::Length(7),
+                Constraint::Length(7),
+                Constraint::Min(4),
+                Constraint::Length(9),
+            ],
+        )
+        .header(header)
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Double)
+                .title(title),
+        )
+        .row_highlight_style(Style::default().on_white().black())
+    }
+}
+
+#[derive(Clone)]
+struct DnsLookup {
+    hostname: String,
+    record_type: &'static str,
+    response: DnsResponse,
+    fake_ip_range: String,
+}
+
+impl DnsLookup {
+    fn records(&self) -> Vec<(&'static str, &DnsAnswer)> {
+        let sections = [
+            ("Answer", &self.response.answer),
+            ("Authority", &self.response.authority),
+            ("Additional", &self.response.additional),
+        ];
+
+        sections
+            .into_iter()
+            .flat_map(|(section, records)| records.iter().map(move |r| (section, r)))
+            .collect()
+    }
+
+    fn is_fake_ip(&self, record: &DnsAnswer) -> bool {
+        match record.data.parse::<IpAddr>() {
+            Ok(ip) => in_cidr(ip, &self.fake_ip_range),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Looks up `dns.fake-ip-range` in the generated config, the core falls back
+/// to its default range when it is not set
+async fn read_fake_ip_range(data_dir: Option<String>) -> String {
+    let Some(data_dir) = data_dir else {
+        return DEFAULT_FAKE_IP_RANGE.into();
+    };
+    let path = PathBuf::from(data_dir).join("config.yaml");
+
+    tokio::fs::read_to_string(path)
+        .await
+        .ok()
+        .and_then(|raw| serde_yaml::from_str::<Value>(&raw).ok())
+        .and_then(|value| {
+            value
+                .get("dns")?
+                .get("fake-ip-range")?
+                .as_str()
+                .map(|s| s.to_owned())
+        })
+        .unwrap_or(DEFAULT_FAKE_IP_RANGE.into())
+}
+
+fn in_cidr(ip: IpAddr, cidr: &str) -> bool {
+    let Some((network, prefix)) = cidr.split_once('/') else {
+        return false;
+    };
+    let (Ok(network), Ok(prefix)) = (network.parse::<IpAddr>(), prefix.parse::<u32>()) else {
+        return false;
+    };
+
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) if prefix <= 32 => {
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) if prefix <= 128 => {
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+fn record_type_name(record_type: u16) -> String {
+    match record_type {
+        1 => "A".into(),
+        2 => "NS".into(),
+        5 => "CNAME".into(),
+        6 => "SOA".into(),
+        12 => "PTR".into(),
+        15 => "MX".into(),
+        16 => "TXT".into(),
+        28 => "AAAA".into(),
+        33 => "SRV".into(),
+        65 => "HTTPS".into(),
+        other => other.to_string(),
+    }
+}
+
+fn rcode_name(status: u32) -> String {
+    match status {
+        0 => "NOERROR".into(),
+        1 => "FORMERR".into(),
+        2 => "SERVFAIL".into(),
+        3 => "NXDOMAIN".into(),
+        4 => "NOTIMP".into(),
+        5 => "REFUSED".into(),
+        other => format!("RCODE {}", other),
+    }
+}
@@ -0,0 +1,88 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    style::Stylize,
+    text::{Line, Span},
+};
+
+#[derive(Clone, Default)]
+pub struct Input {
+    value: String,
+    cursor: usize,
+}
+
+impl Input {
+    pub fn new<S>(value: S) -> Self
+    where
+        S: Into<String>,
+    {
+        let value = value.into();
+        let cursor = value.chars().count();
+
+        Self { value, cursor }
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn clear(&mut self) {
+        self.value.clear();
+        self.cursor = 0;
+    }
+
+    /// Applies an editing key, returns `false` if the key is not an editing key
+    pub fn handle_key(&mut self, key: &KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                let index = self.byte_index();
+                self.value.insert(index, c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace => {
+                if self.cursor > 0 {
+                    self.cursor -= 1;
+                    let index = self.byte_index();
+                    self.value.remove(index);
+                }
+            }
+            KeyCode::Delete => {
+                if self.cursor < self.value.chars().count() {
+                    let index = self.byte_index();
+                    self.value.remove(index);
+                }
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.value.chars().count()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.value.chars().count(),
+            _ => return false,
+        }
+
+        true
+    }
+
+    pub fn create_line(&self, focused: bool) -> Line<'static> {
+        if !focused {
+            return Line::from(self.value.clone());
+        }
+
+        let index = self.byte_index();
+        let (before, after) = self.value.split_at(index);
+        let mut after = after.chars();
+        let under_cursor = after.next().map(|c| c.to_string()).unwrap_or(" ".into());
+
+        Line::from(vec![
+            Span::from(before.to_owned()),
+            Span::from(under_cursor).reversed(),
+            Span::from(after.collect::<String>()),
+        ])
+    }
+
+    fn byte_index(&self) -> usize {
+        self.value
+            .char_indices()
+            .nth(self.cursor)
+            .map(|(i, _)| i)
+            .unwrap_or(self.value.len())
+    }
+}
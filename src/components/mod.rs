@@ -1,34 +1,73 @@
+mod connections;
+mod dns;
+mod input;
 mod profiles;
+mod proxies;
+mod rules;
+mod settings;
 mod status;
+#[cfg(test)]
+mod tests;
+
+use std::cell::RefCell;
 
 use anyhow::Result;
+use connections::Connections;
 use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers};
+use dns::Dns;
 use profiles::Profile;
+use proxies::Proxies;
 use ratatui::{
-    layout::{Constraint, Layout, Rect},
+    layout::{Constraint, Flex, Layout, Rect},
     style::{Style, Stylize},
-    widgets::{Block, BorderType, Borders, Paragraph, Tabs},
+    text::{Line, Text},
+    widgets::{Block, BorderType, Borders, Cell, Clear, Paragraph, Row, Table, TableState, Tabs},
     Frame,
 };
+use rules::Rules;
+use settings::Settings;
 use status::Status;
 
-use crate::app::App;
+use crate::{
+    app::App,
+    config::{profile::ProfileManager, tui::TuiConfig},
+    task::{TaskEvent, TaskManager},
+    utils::logger::{LogLevel, Logger},
+};
 
 pub trait Component {
     fn new() -> Self;
     fn render(&self, area: &Rect, frame: &mut Frame);
     async fn tick(&mut self) -> Result<()>;
     async fn handle_event(&mut self, ev: &Event) -> Result<()>;
+
+    async fn handle_task(&mut self, _: &TaskEvent) -> Result<()> {
+        Ok(())
+    }
+
+    fn is_editing(&self) -> bool {
+        false
+    }
 }
 
+const TICKS_PER_SCHEDULE: u64 = 5 * 60;
+
 pub struct Root {
     main_component: RootMainComponent,
+    ticks: u64,
+    scheduling: Option<u64>,
+    task_state: Option<RefCell<TableState>>,
+    controller_state: Option<RefCell<TableState>>,
 }
 
 impl Component for Root {
     fn new() -> Self {
         Self {
             main_component: RootMainComponent::Status(Status::new()),
+            ticks: 0,
+            scheduling: None,
+            task_state: None,
+            controller_state: None,
         }
     }
 
@@ -40,74 +79,300 @@ impl Component for Root {
         ])
         .areas(frame.area());
 
+        let [tabs_area, controller_area, task_count_area] = Layout::horizontal(vec![
+            Constraint::Min(0),
+            Constraint::Length(24),
+            Constraint::Length(18),
+        ])
+        .areas(tabs_area);
+
         frame.render_widget(self.create_tabs(), tabs_area);
+        frame.render_widget(self.create_controller_name(), controller_area);
+        frame.render_widget(self.create_task_count(), task_count_area);
+        let warning = Self::create_warning();
+        let [help_area, warning_area] = Layout::horizontal(vec![
+            Constraint::Min(0),
+            Constraint::Length(warning.as_ref().map_or(0, |w| w.width() as u16)),
+        ])
+        .areas(help_area);
         frame.render_widget(self.create_help(), help_area);
+        if let Some(warning) = warning {
+            frame.render_widget(warning, warning_area);
+        }
 
         match &self.main_component {
+            RootMainComponent::Status(c) => c.render(&main_area, frame),
             RootMainComponent::Profiles(c) => c.render(&main_area, frame),
-            _ => (),
+            RootMainComponent::Proxies(c) => c.render(&main_area, frame),
+            RootMainComponent::Rules(c) => c.render(&main_area, frame),
+            RootMainComponent::Settings(c) => c.render(&main_area, frame),
+            RootMainComponent::Connections(c) => c.render(&main_area, frame),
+            RootMainComponent::Dns(c) => c.render(&main_area, frame),
+        }
+
+        if let Some(task_state) = &self.task_state {
+            let popup_area = create_popup_area(&main_area, 64, 16);
+            frame.render_widget(Clear, popup_area);
+            frame.render_stateful_widget(
+                self.create_task_table(),
+                popup_area,
+                &mut task_state.borrow_mut(),
+            );
+        }
+
+        if let Some(controller_state) = &self.controller_state {
+            let popup_area = create_popup_area(&main_area, 64, 16);
+            frame.render_widget(Clear, popup_area);
+            frame.render_stateful_widget(
+                self.create_controller_table(),
+                popup_area,
+                &mut controller_state.borrow_mut(),
+            );
         }
     }
 
     async fn tick(&mut self) -> Result<()> {
+        if self.ticks.is_multiple_of(TICKS_PER_SCHEDULE)
+            && self.scheduling.is_none()
+            && ProfileManager::has_due()
+        {
+            self.scheduling = Some(
+                TaskManager::get_instance()
+                    .spawn("Scheduled profile updates", Self::update_due_profiles()),
+            );
+        }
+        self.ticks += 1;
+
         match &mut self.main_component {
+            RootMainComponent::Status(c) => c.tick().await?,
             RootMainComponent::Profiles(c) => c.tick().await?,
-            _ => (),
+            RootMainComponent::Proxies(c) => c.tick().await?,
+            RootMainComponent::Rules(c) => c.tick().await?,
+            RootMainComponent::Settings(c) => c.tick().await?,
+            RootMainComponent::Connections(c) => c.tick().await?,
+            RootMainComponent::Dns(c) => c.tick().await?,
         }
 
         Ok(())
     }
 
     async fn handle_event(&mut self, ev: &Event) -> Result<()> {
-        match ev {
-            Event::Key(key) => match key.kind {
-                KeyEventKind::Press => match key.code {
-                    KeyCode::Esc => *App::get_instance().running.lock().unwrap() = false,
-                    KeyCode::Char('c') | KeyCode::Char('C') => {
-                        if key.modifiers == KeyModifiers::CONTROL {
-                            *App::get_instance().running.lock().unwrap() = false;
-                        }
+        if self.task_state.is_some() {
+            if let Event::Key(key) = ev {
+                if key.kind == KeyEventKind::Press {
+                    self.handle_task_list_key(key.code);
+                }
+            }
+
+            return Ok(());
+        }
+
+        if self.controller_state.is_some() {
+            if let Event::Key(key) = ev {
+                if key.kind == KeyEventKind::Press {
+                    self.handle_controller_list_key(key.code).await;
+                }
+            }
+
+            return Ok(());
+        }
+
+        if let Event::Key(key) = ev {
+            // Text inputs of the main component get the keys first
+            let editing = self.is_editing();
+
+            if key.kind == KeyEventKind::Press {
+                match key.code {
+                    KeyCode::Esc if !editing => {
+                        *App::get_instance().running.lock().unwrap() = false
+                    }
+                    KeyCode::Char('c') | KeyCode::Char('C')
+                        if key.modifiers == KeyModifiers::CONTROL =>
+                    {
+                        *App::get_instance().running.lock().unwrap() = false;
+                    }
+                    KeyCode::F(9) if !editing => {
+                        let selected = TuiConfig::global().lock().unwrap().controller_index();
+                        self.controller_state =
+                            Some(RefCell::new(TableState::new().with_selected(selected)));
+                        return Ok(());
                     }
-                    KeyCode::F(f) => match f {
-                        1 => {
-                            if self.main_component.as_usize() != 0 {
-                                self.main_component = RootMainComponent::Status(Status::new());
-                            }
-                        }
-                        2 => {
-                            if self.main_component.as_usize() != 1 {
-                                self.main_component = RootMainComponent::Profiles(Profile::new());
-                            }
-                        }
-                        3 => (),
-                        4 => (),
-                        5 => (),
-                        _ => (),
-                    },
+                    KeyCode::F(10) if !editing => {
+                        self.task_state = Some(RefCell::new(TableState::new().with_selected(0)));
+                        return Ok(());
+                    }
+                    KeyCode::F(f) if !editing => self.switch_tab(f),
                     _ => (),
-                },
-                _ => (),
-            },
-            _ => (),
+                }
+            }
         }
 
         match &mut self.main_component {
+            RootMainComponent::Status(c) => c.handle_event(ev).await?,
             RootMainComponent::Profiles(c) => c.handle_event(ev).await?,
-            _ => (),
+            RootMainComponent::Proxies(c) => c.handle_event(ev).await?,
+            RootMainComponent::Rules(c) => c.handle_event(ev).await?,
+            RootMainComponent::Settings(c) => c.handle_event(ev).await?,
+            RootMainComponent::Connections(c) => c.handle_event(ev).await?,
+            RootMainComponent::Dns(c) => c.handle_event(ev).await?,
+        }
+
+        Ok(())
+    }
+
+    async fn handle_task(&mut self, ev: &TaskEvent) -> Result<()> {
+        if self.scheduling == Some(ev.id) {
+            self.scheduling = None;
+
+            if let Err(err) = &ev.result {
+                Logger::get_instance()
+                    .lock()
+                    .unwrap()
+                    .error(format!("could not run scheduled updates: {:#}", err));
+            }
+            return Ok(());
+        }
+
+        match &mut self.main_component {
+            RootMainComponent::Status(c) => c.handle_task(ev).await?,
+            RootMainComponent::Profiles(c) => c.handle_task(ev).await?,
+            RootMainComponent::Proxies(c) => c.handle_task(ev).await?,
+            RootMainComponent::Rules(c) => c.handle_task(ev).await?,
+            RootMainComponent::Settings(c) => c.handle_task(ev).await?,
+            RootMainComponent::Connections(c) => c.handle_task(ev).await?,
+            RootMainComponent::Dns(c) => c.handle_task(ev).await?,
         }
 
         Ok(())
     }
+
+    fn is_editing(&self) -> bool {
+        match &self.main_component {
+            RootMainComponent::Profiles(c) => c.is_editing(),
+            RootMainComponent::Rules(c) => c.is_editing(),
+            RootMainComponent::Settings(c) => c.is_editing(),
+            RootMainComponent::Connections(c) => c.is_editing(),
+            RootMainComponent::Dns(c) => c.is_editing(),
+            _ => false,
+        }
+    }
 }
 
 impl Root {
-    fn create_tabs(&self) -> Tabs {
+    async fn update_due_profiles() -> Result<()> {
+        let results = ProfileManager::update_due().await?;
+        let mut logger = Logger::get_instance().lock().unwrap();
+
+        for (name, result) in results {
+            match result {
+                Ok(_) => logger.info(format!("Profile \"{}\" updated on schedule", name)),
+                Err(err) => logger.error(format!(
+                    "could not update profile \"{}\" on schedule: {:#}",
+                    name, err
+                )),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn switch_tab(&mut self, f: u8) {
+        if self.main_component.as_usize() + 1 == f as usize {
+            return;
+        }
+
+        self.open_tab(f);
+    }
+
+    fn open_tab(&mut self, f: u8) {
+        self.main_component = match f {
+            1 => RootMainComponent::Status(Status::new()),
+            2 => RootMainComponent::Profiles(Profile::new()),
+            3 => RootMainComponent::Proxies(Proxies::new()),
+            4 => RootMainComponent::Rules(Rules::new()),
+            5 => RootMainComponent::Settings(Box::new(Settings::new())),
+            6 => RootMainComponent::Connections(Connections::new()),
+            7 => RootMainComponent::Dns(Dns::new()),
+            _ => return,
+        };
+    }
+
+    fn handle_task_list_key(&mut self, code: KeyCode) {
+        let tasks = TaskManager::get_instance().get_tasks();
+        let mut state = self.task_state.as_ref().unwrap().borrow_mut();
+        let selected = state.selected().unwrap_or(0);
+
+        match code {
+            KeyCode::Up => state.select(Some(selected.saturating_sub(1))),
+            KeyCode::Down if selected + 1 < tasks.len() => state.select(Some(selected + 1)),
+            KeyCode::Char('x') | KeyCode::Char('X') | KeyCode::Delete => {
+                // The owner is notified and logs the cancellation itself
+                if let Some(task) = tasks.get(selected) {
+                    TaskManager::get_instance().cancel(task.id);
+                }
+            }
+            KeyCode::Esc | KeyCode::F(10) => {
+                drop(state);
+                self.task_state = None;
+            }
+            _ => (),
+        }
+    }
+
+    async fn handle_controller_list_key(&mut self, code: KeyCode) {
+        let count = TuiConfig::global().lock().unwrap().controllers.len();
+        let state = self.controller_state.as_ref().unwrap();
+        let selected = state.borrow().selected().unwrap_or(0);
+
+        match code {
+            KeyCode::Up => state.borrow_mut().select(Some(selected.saturating_sub(1))),
+            KeyCode::Down if selected + 1 < count => state.borrow_mut().select(Some(selected + 1)),
+            KeyCode::Enter => {
+                self.controller_state = None;
+                self.switch_controller(selected).await;
+            }
+            KeyCode::Esc | KeyCode::F(9) => self.controller_state = None,
+            _ => (),
+        }
+    }
+
+    async fn switch_controller(&mut self, index: usize) {
+        let name = {
+            let config = TuiConfig::global().lock().unwrap();
+            if config.controller_index() == index {
+                return;
+            }
+            match config.controllers.get(index) {
+                Some(controller) => controller.name.clone(),
+                None => return,
+            }
+        };
+
+        if let Err(err) = TuiConfig::set_active_controller(&name).await {
+            Logger::get_instance()
+                .lock()
+                .unwrap()
+                .error(format!("could not switch controller: {:#}", err));
+            return;
+        }
+
+        // Results of the previous instance are dropped with the old view
+        Logger::get_instance()
+            .lock()
+            .unwrap()
+            .info(format!("Switched to controller `{}`", name));
+        self.open_tab(self.main_component.as_usize() as u8 + 1);
+    }
+
+    fn create_tabs(&self) -> Tabs<'_> {
         Tabs::new(vec![
             "[F1]Status",
             "[F2]Profiles",
             "[F3]Proxies",
             "[F4]Rules",
             "[F5]Settings",
+            "[F6]Connections",
+            "[F7]DNS",
         ])
         .block(
             Block::new()
@@ -120,7 +385,121 @@ impl Root {
         .divider("")
     }
 
-    fn create_help(&self) -> Paragraph {
+    fn create_controller_name(&self) -> Paragraph<'_> {
+        let name = TuiConfig::global()
+            .lock()
+            .unwrap()
+            .controller()
+            .name
+            .clone();
+
+        Paragraph::new(Text::from(format!("[F9]{}", name)).right_aligned()).dark_gray()
+    }
+
+    fn create_task_count(&self) -> Paragraph<'_> {
+        let count = TaskManager::get_instance().get_tasks().len();
+        let text = Text::from(format!("[F10]Tasks: {}", count)).right_aligned();
+
+        if count > 0 {
+            Paragraph::new(text).light_yellow()
+        } else {
+            Paragraph::new(text).dark_gray()
+        }
+    }
+
+    fn create_task_table(&self) -> Table<'_> {
+        let header = Row::new(
+            ["Name", "Elapsed"]
+                .into_iter()
+                .map(|s| Cell::new(Text::from(s).centered()).on_blue())
+                .collect::<Vec<Cell>>(),
+        )
+        .on_light_blue()
+        .white()
+        .bold();
+
+        let rows = TaskManager::get_instance()
+            .get_tasks()
+            .into_iter()
+            .map(|t| {
+                Row::new(vec![
+                    Cell::new(t.name),
+                    Cell::new(
+                        Text::from(format!("{:.1}s", t.started_at.elapsed().as_secs_f64()))
+                            .right_aligned(),
+                    ),
+                ])
+            })
+            .collect::<Vec<Row>>();
+
+        Table::new(rows, vec![Constraint::Min(4), Constraint::Length(10)])
+            .header(header)
+            .block(
+                Block::bordered()
+                    .border_type(BorderType::Double)
+                    .title(" Tasks ")
+                    .title_bottom(" [UP/DOWN]Move cursor  [X]Cancel  [ESC]Close "),
+            )
+            .row_highlight_style(Style::default().on_white().black())
+    }
+
+    fn create_controller_table(&self) -> Table<'_> {
+        let header = Row::new(
+            ["", "Name", "API"]
+                .into_iter()
+                .map(|s| Cell::new(Text::from(s).centered()).on_blue())
+                .collect::<Vec<Cell>>(),
+        )
+        .on_light_blue()
+        .white()
+        .bold();
+
+        let config = TuiConfig::global().lock().unwrap();
+        let active = config.controller_index();
+        let rows = config
+            .controllers
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                Row::new(vec![
+                    Cell::new(if i == active { "*" } else { "" }),
+                    Cell::new(c.name.clone()),
+                    Cell::new(c.api.clone()),
+                ])
+            })
+            .collect::<Vec<Row>>();
+
+        Table::new(
+            rows,
+            vec![
+                Constraint::Length(1),
+                Constraint::Length(16),
+                Constraint::Min(4),
+            ],
+        )
+        .header(header)
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Double)
+                .title(" Controllers ")
+                .title_bottom(" [UP/DOWN]Move cursor  [ENTER]Switch  [ESC]Close "),
+        )
+        .row_highlight_style(Style::default().on_white().black())
+    }
+
+    /// Subscriptions running out, so they are noticed from any tab
+    fn create_warning() -> Option<Line<'static>> {
+        let warnings = ProfileManager::get_warnings();
+        let text = match warnings.len() {
+            0 => return None,
+            1 => format!(" {} ", warnings[0]),
+            n => format!(" {} (+{} more) ", warnings[0], n - 1),
+        };
+
+        Some(Line::from(text).on_light_red().white().bold())
+    }
+
+    fn create_help(&self) -> Paragraph<'_> {
         Paragraph::new(App::get_instance().help_text.lock().unwrap().clone())
             .on_white()
             .black()
@@ -131,9 +510,11 @@ impl Root {
 enum RootMainComponent {
     Status(Status),
     Profiles(Profile),
-    Proxies,
-    Rules,
-    Settings,
+    Proxies(Proxies),
+    Rules(Rules),
+    Settings(Box<Settings>),
+    Connections(Connections),
+    Dns(Dns),
 }
 
 impl RootMainComponent {
@@ -141,9 +522,44 @@ impl RootMainComponent {
         match self {
             Self::Status(_) => 0,
             Self::Profiles(_) => 1,
-            Self::Proxies => 2,
-            Self::Rules => 3,
-            Self::Settings => 4,
+            Self::Proxies(_) => 2,
+            Self::Rules(_) => 3,
+            Self::Settings(_) => 4,
+            Self::Connections(_) => 5,
+            Self::Dns(_) => 6,
         }
     }
 }
+
+fn create_popup_area(area: &Rect, width: u16, height: u16) -> Rect {
+    let [area] = Layout::horizontal([Constraint::Length(width)])
+        .flex(Flex::Center)
+        .areas(*area);
+    let [area] = Layout::vertical([Constraint::Length(height)])
+        .flex(Flex::Center)
+        .areas(area);
+
+    area
+}
+
+fn create_log<'a>(height: u16) -> Paragraph<'a> {
+    let logger = Logger::get_instance().lock().unwrap();
+    let lines = logger
+        .get_buffer()
+        .iter()
+        .map(|(log_level, text)| match log_level {
+            LogLevel::Info => Line::from(text.clone()).green(),
+            LogLevel::Warn => Line::from(text.clone()).yellow(),
+            LogLevel::Error => Line::from(text.clone()).red(),
+        })
+        .collect::<Vec<Line>>();
+    let line_len = lines.len() as u16;
+
+    Paragraph::new(lines)
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Double)
+                .title(" Log "),
+        )
+        .scroll((line_len.saturating_sub(height), 0))
+}
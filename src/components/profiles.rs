@@ -1,34 +1,48 @@
 use std::cell::RefCell;
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
 use chrono::{TimeZone, Utc};
-use crossterm::event::{Event, KeyCode, KeyEventKind};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use ratatui::{
     layout::{Constraint, Layout, Rect},
     style::{Style, Stylize},
-    text::{Line, Text},
-    widgets::{Block, BorderType, Cell, Paragraph, Row, Table, TableState},
+    text::{Line, Span, Text},
+    widgets::{Block, BorderType, Cell, Clear, Paragraph, Row, Table, TableState, Wrap},
     Frame,
 };
+use reqwest::Url;
+use tokio::fs;
 
-use crate::{app::App, config::profile::ProfileManager, utils::logger::{LogLevel, Logger}};
+use crate::{
+    app::App,
+    config::{
+        profile::{self, ProfileManager, ProfileRemote},
+        tui::TuiConfig,
+    },
+    task::{TaskEvent, TaskManager},
+    utils::{format::format_bytes, logger::Logger, subscription},
+};
 
-use super::Component;
+use super::{create_log, create_popup_area, input::Input, Component};
 
 pub struct Profile {
     table_state: RefCell<TableState>,
+    tasks: Vec<u64>,
+    modal: Option<ProfileModal>,
 }
 
 impl Component for Profile {
     fn new() -> Self {
         *App::get_instance().help_text.lock().unwrap() =
-            "[ESC]Quit  [UP/DOWN]Move cursor  [ENTER]Activate  [A]Add  [D]Delete  [E]Edit  [U]Update".into();
+            "[ESC]Quit  [UP/DOWN]Move cursor  [ENTER]Activate  [A]Add  [D]Delete  [E]Edit  [U]Update  [SHIFT-U]Update all".into();
 
         let mut table_state = TableState::new();
         table_state.select(Some(0));
 
         Self {
             table_state: RefCell::new(table_state),
+            tasks: Vec::new(),
+            modal: None,
         }
     }
 
@@ -41,7 +55,22 @@ impl Component for Profile {
             table_area,
             &mut self.table_state.borrow_mut(),
         );
-        frame.render_widget(self.create_log(log_area.height), log_area);
+        frame.render_widget(create_log(log_area.height), log_area);
+
+        match &self.modal {
+            Some(ProfileModal::Form(form)) => {
+                let popup_area = create_popup_area(area, 72, 13);
+                frame.render_widget(Clear, popup_area);
+                frame.render_widget(form.create_form(), popup_area);
+            }
+            Some(ProfileModal::ConfirmDelete(uuid)) => {
+                let name = find_profile(uuid).map(|p| p.name).unwrap_or_default();
+                let popup_area = create_popup_area(area, 48, 5);
+                frame.render_widget(Clear, popup_area);
+                frame.render_widget(Self::create_confirm_delete(name), popup_area);
+            }
+            None => (),
+        }
     }
 
     async fn tick(&mut self) -> Result<()> {
@@ -49,101 +78,424 @@ impl Component for Profile {
     }
 
     async fn handle_event(&mut self, ev: &Event) -> Result<()> {
-        match ev {
-            Event::Key(key) => match key.kind {
-                KeyEventKind::Press => match key.code {
-                    KeyCode::Up => {
-                        let mut state = self.table_state.borrow_mut();
-                        let selected = state.selected().unwrap();
-
-                        if selected > 0 {
-                            state.select(Some(selected - 1));
-                        }
+        let Event::Key(key) = ev else {
+            return Ok(());
+        };
+        if key.kind != KeyEventKind::Press {
+            return Ok(());
+        }
+
+        if self.modal.is_some() {
+            return self.handle_modal_key(key).await;
+        }
+
+        match key.code {
+            KeyCode::Up => {
+                let mut state = self.table_state.borrow_mut();
+                let selected = state.selected().unwrap();
+
+                if selected > 0 {
+                    state.select(Some(selected - 1));
+                }
+            }
+            KeyCode::Down => {
+                let total = ProfileManager::get_all().lock().unwrap().len();
+                let mut state = self.table_state.borrow_mut();
+                let selected = state.selected().unwrap();
+
+                if selected < total {
+                    state.select(Some(selected + 1));
+                }
+            }
+            KeyCode::Enter => {
+                let selected = self.table_state.borrow().selected().unwrap();
+
+                if selected == 0 {
+                    Logger::get_instance()
+                        .lock()
+                        .unwrap()
+                        .info("Activating fallback profile");
+
+                    self.tasks.push(TaskManager::get_instance().spawn(
+                        "Activate fallback profile",
+                        ProfileManager::active_fallback_profile(),
+                    ));
+                } else {
+                    let profile = ProfileManager::get_all().lock().unwrap()[selected - 1].clone();
+
+                    Logger::get_instance()
+                        .lock()
+                        .unwrap()
+                        .info(format!("Activating profile \"{}\"", profile.name));
+
+                    self.tasks.push(TaskManager::get_instance().spawn(
+                        format!("Activate profile \"{}\"", profile.name),
+                        async move { profile.activate().await },
+                    ));
+                }
+            }
+            KeyCode::Char('a') | KeyCode::Char('A') => {
+                self.modal = Some(ProfileModal::Form(Box::new(ProfileForm::new(None))));
+            }
+            KeyCode::Char('e') | KeyCode::Char('E') => {
+                if let Some(uuid) = self.get_selected_uuid() {
+                    self.modal = Some(ProfileModal::Form(Box::new(ProfileForm::new(Some(uuid)))));
+                }
+            }
+            KeyCode::Char('d') | KeyCode::Char('D') => {
+                if let Some(uuid) = self.get_selected_uuid() {
+                    if let Err(err) = Self::check_deletable(&uuid) {
+                        Logger::get_instance().lock().unwrap().warn(err.to_string());
+                    } else {
+                        self.modal = Some(ProfileModal::ConfirmDelete(uuid));
                     }
-                    KeyCode::Down => {
-                        let total = ProfileManager::get_all().lock().unwrap().len();
-                        let mut state = self.table_state.borrow_mut();
-                        let selected = state.selected().unwrap();
+                }
+            }
+            KeyCode::Char('U') if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                self.spawn_update_all()
+            }
+            // Without SHIFT an uppercase `U` comes from caps lock
+            KeyCode::Char('u') | KeyCode::Char('U') => {
+                let selected = self.table_state.borrow().selected().unwrap();
 
-                        if selected < total {
-                            state.select(Some(selected + 1));
-                        }
+                if selected != 0 {
+                    let profile = ProfileManager::get_all().lock().unwrap()[selected - 1].clone();
+                    self.spawn_update(profile);
+                }
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    async fn handle_task(&mut self, ev: &TaskEvent) -> Result<()> {
+        if let Some(index) = self.tasks.iter().position(|id| *id == ev.id) {
+            self.tasks.remove(index);
+
+            if let Err(err) = &ev.result {
+                Logger::get_instance()
+                    .lock()
+                    .unwrap()
+                    .error(format!("{:#}", err));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_editing(&self) -> bool {
+        self.modal.is_some()
+    }
+}
+
+impl Profile {
+    fn spawn_update(&mut self, profile: profile::Profile) {
+        if profile.remote.is_none() {
+            Logger::get_instance().lock().unwrap().warn(format!(
+                "Profile \"{}\" is local and could not be updated",
+                profile.name
+            ));
+            return;
+        }
+        if profile.updating {
+            return;
+        }
+
+        Logger::get_instance()
+            .lock()
+            .unwrap()
+            .info(format!("Updating profile \"{}\"", profile.name));
+
+        self.tasks.push(TaskManager::get_instance().spawn(
+            format!("Update profile \"{}\"", profile.name),
+            async move {
+                ProfileManager::update(&profile.uuid)
+                    .await
+                    .with_context(|| format!("could not update profile \"{}\"", profile.name))?;
+
+                Logger::get_instance()
+                    .lock()
+                    .unwrap()
+                    .info(format!("Profile \"{}\" updated", profile.name));
+
+                Ok(())
+            },
+        ));
+    }
+
+    fn spawn_update_all(&mut self) {
+        Logger::get_instance()
+            .lock()
+            .unwrap()
+            .info("Updating all remote profiles");
+
+        self.tasks.push(
+            TaskManager::get_instance().spawn("Update all profiles", async move {
+                let results = ProfileManager::update_all().await?;
+                let mut logger = Logger::get_instance().lock().unwrap();
+
+                for (name, result) in results {
+                    match result {
+                        Ok(_) => logger.info(format!("Profile \"{}\" updated", name)),
+                        Err(err) => logger
+                            .error(format!("could not update profile \"{}\": {:#}", name, err)),
                     }
-                    KeyCode::Enter => {
-                        let selected = self.table_state.borrow().selected().unwrap();
-
-                        if selected == 0 {
-                            Logger::get_instance()
-                                .lock()
-                                .unwrap()
-                                .info("Activating fallback profile");
-
-                            if let Err(err) = ProfileManager::active_fallback_profile().await {
-                                Logger::get_instance()
-                                    .lock()
-                                    .unwrap()
-                                    .error(format!("{:#}", err));
-                            }
-                        } else {
-                            let mut profiles = ProfileManager::get_all().lock().unwrap();
-                            let profile = profiles.get_mut(selected - 1).unwrap();
+                }
 
-                            Logger::get_instance()
-                                .lock()
-                                .unwrap()
-                                .info(format!("Activating profile \"{}\"", profile.name));
+                Ok(())
+            }),
+        );
+    }
 
-                            if let Err(err) = profile.activate().await {
-                                Logger::get_instance()
-                                    .lock()
-                                    .unwrap()
-                                    .error(format!("{:#}", err));
+    fn get_selected_uuid(&self) -> Option<String> {
+        let selected = self.table_state.borrow().selected().unwrap();
+
+        if selected == 0 {
+            Logger::get_instance()
+                .lock()
+                .unwrap()
+                .warn("The fallback profile is builtin and could not be modified");
+            None
+        } else {
+            ProfileManager::get_all()
+                .lock()
+                .unwrap()
+                .get(selected - 1)
+                .map(|p| p.uuid.clone())
+        }
+    }
+
+    async fn handle_modal_key(&mut self, key: &KeyEvent) -> Result<()> {
+        match self.modal.as_mut().unwrap() {
+            ProfileModal::Form(form) => {
+                if form.handle_key(key) {
+                    return Ok(());
+                }
+
+                match key.code {
+                    KeyCode::Esc => self.modal = None,
+                    KeyCode::Char('s') | KeyCode::Char('S') => {
+                        let form = form.as_ref().clone();
+                        match self.save_form(&form).await {
+                            Ok(_) => self.modal = None,
+                            Err(err) => {
+                                if let Some(ProfileModal::Form(form)) = &mut self.modal {
+                                    form.error = Some(format!("{:#}", err));
+                                }
                             }
                         }
                     }
-                    KeyCode::Char('u') | KeyCode::Char('U') => {
-                        let selected = self.table_state.borrow().selected().unwrap();
-
-                        if selected != 0 {
-                            ProfileManager::get_all()
-                                .lock()
-                                .unwrap()
-                                .get_mut(selected - 1)
-                                .unwrap()
-                                .activate()
-                                .await?;
-                        }
+                    _ => (),
+                }
+            }
+            ProfileModal::ConfirmDelete(uuid) => {
+                let uuid = uuid.clone();
+
+                match key.code {
+                    KeyCode::Char('y') | KeyCode::Char('Y') => {
+                        self.modal = None;
+                        self.delete_profile(&uuid).await;
                     }
+                    KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => self.modal = None,
                     _ => (),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn save_form(&mut self, form: &ProfileForm) -> Result<()> {
+        let name = form.name.value().trim().to_owned();
+        if name.is_empty() {
+            bail!("name could not be empty");
+        }
+
+        let mut profile = match &form.uuid {
+            Some(uuid) => find_profile(uuid)?,
+            None => profile::Profile::default(),
+        };
+        profile.name = name;
+        profile.remote = if form.remote {
+            let url = form.url.value().trim();
+            match Url::parse(url) {
+                Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => (),
+                _ => bail!("URL `{}` is not a valid http(s) URL", url),
+            }
+
+            let update_interval = match form.update_interval.value().trim() {
+                "" => None,
+                value => match value.parse::<u64>() {
+                    Ok(minutes) if minutes > 0 => Some(minutes),
+                    _ => bail!("update interval `{}` is not a number of minutes", value),
                 },
-                _ => (),
-            },
-            _ => (),
+            };
+
+            Some(ProfileRemote {
+                url: url.to_owned(),
+                user_agent: form.user_agent.value().trim().to_owned(),
+                use_system_proxy: form.use_system_proxy,
+                use_mihomo_proxy: form.use_mihomo_proxy,
+                allow_invalid_certificates: form.allow_invalid_certificates,
+                update_interval,
+            })
+        } else {
+            None
+        };
+
+        // Import local contents, or start from an empty config
+        let import_file = form.import_file.value().trim();
+        if !form.remote && !import_file.is_empty() {
+            let contents = fs::read_to_string(import_file)
+                .await
+                .with_context(|| format!("could not read file `{}`", import_file))?;
+            let contents = subscription::convert(&contents)
+                .with_context(|| format!("file `{}` is not a valid profile", import_file))?;
+            profile.write_raw(&contents).await?;
+        } else if form.uuid.is_none() && !form.remote {
+            profile.write_raw("{}\n").await?;
         }
 
+        {
+            // Only copy the edited fields, an update may have run meanwhile
+            let mut profiles = ProfileManager::get_all().lock().unwrap();
+            match &form.uuid {
+                Some(uuid) => match profiles.iter_mut().find(|p| p.uuid == *uuid) {
+                    Some(p) => {
+                        p.name = profile.name.clone();
+                        p.remote = profile.remote.clone();
+                    }
+                    None => bail!("profile \"{}\" no longer exists", profile.name),
+                },
+                None => profiles.push(profile.clone()),
+            }
+        }
+        ProfileManager::flush_all().await?;
+
+        Logger::get_instance()
+            .lock()
+            .unwrap()
+            .info(match form.uuid {
+                Some(_) => format!("Profile \"{}\" saved", profile.name),
+                None => format!("Profile \"{}\" added", profile.name),
+            });
+
         Ok(())
     }
-}
 
-impl Profile {
-    fn create_table(&self) -> Table {
+    /// The active profile backs the running core, and updates still write its files
+    fn check_deletable(uuid: &str) -> Result<()> {
+        let profile = find_profile(uuid)?;
+        let active = TuiConfig::global().lock().unwrap().active_profile.clone();
+
+        if active.as_ref() == Some(&profile.uuid) {
+            bail!(
+                "Profile \"{}\" is active, activate another one before deleting it",
+                profile.name
+            );
+        }
+        if profile.updating {
+            bail!(
+                "Profile \"{}\" is updating, wait for the update to finish",
+                profile.name
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn delete_profile(&mut self, uuid: &str) {
+        // The profile may have been activated or started updating meanwhile
+        if let Err(err) = Self::check_deletable(uuid) {
+            Logger::get_instance().lock().unwrap().warn(err.to_string());
+            return;
+        }
+
+        let profile = {
+            let mut profiles = ProfileManager::get_all().lock().unwrap();
+            let index = profiles.iter().position(|p| p.uuid == uuid).unwrap();
+            profiles.remove(index)
+        };
+
+        let result = async {
+            profile.remove_files().await?;
+            ProfileManager::flush_all().await
+        }
+        .await;
+
+        match result {
+            Ok(_) => Logger::get_instance()
+                .lock()
+                .unwrap()
+                .info(format!("Profile \"{}\" deleted", profile.name)),
+            Err(err) => Logger::get_instance()
+                .lock()
+                .unwrap()
+                .error(format!("{:#}", err)),
+        }
+
+        let total = ProfileManager::get_all().lock().unwrap().len();
+        let mut state = self.table_state.borrow_mut();
+        if state.selected().unwrap() > total {
+            state.select(Some(total));
+        }
+    }
+
+    fn create_confirm_delete(name: String) -> Paragraph<'static> {
+        Paragraph::new(vec![
+            Line::from(format!(" Delete profile \"{}\"?", name)),
+            Line::default(),
+            Line::from(" [Y]Yes  [N]No").dark_gray().italic(),
+        ])
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Double)
+                .title(" Delete Profile ")
+                .border_style(Style::default().light_red()),
+        )
+    }
+
+    fn create_table(&self) -> Table<'_> {
         let header = Row::new(
-            ["Active", "Name", "Type", "Updated At", "Used", "Expired At"]
-                .into_iter()
-                .map(|s| Cell::new(Text::from(s).centered()).on_blue())
-                .collect::<Vec<Cell>>(),
+            [
+                "Active",
+                "Name",
+                "Type",
+                "Updated At",
+                "Used",
+                "Remaining",
+                "Expired At",
+                "Days Left",
+            ]
+            .into_iter()
+            .map(|s| Cell::new(Text::from(s).centered()).on_blue())
+            .collect::<Vec<Cell>>(),
         )
         .on_light_blue()
         .white()
         .bold();
 
+        let (active_profile, warning_days, warning_percent) = {
+            let config = TuiConfig::global().lock().unwrap();
+            (
+                config.active_profile.clone(),
+                config.expiry_warning_days,
+                config.quota_warning_percent,
+            )
+        };
+        let now = profile::unix_now();
         let mut rows = ProfileManager::get_all()
             .lock()
             .unwrap()
             .iter()
             .map(|p| {
                 Row::new(vec![
-                    Cell::default(),
+                    if active_profile.as_ref() == Some(&p.uuid) {
+                        Cell::new(Text::from("X").centered()).green().bold()
+                    } else {
+                        Cell::default()
+                    },
                     if p.updating {
                         Cell::new(p.name.clone()).light_yellow()
                     } else {
@@ -171,10 +523,23 @@ impl Profile {
                             Cell::new(Text::from("None").centered()).dark_gray()
                         }
                     },
-                    if let Some(percent) = &p.get_used_str() {
-                        Cell::new(Text::from(percent.clone()).centered())
-                    } else {
-                        Cell::new(Text::from("N/A").centered()).dark_gray().italic()
+                    match (p.get_used_str(), p.is_over_quota(warning_percent)) {
+                        (Some(percent), true) => {
+                            Cell::new(Text::from(percent).centered()).light_red().bold()
+                        }
+                        (Some(percent), false) => Cell::new(Text::from(percent).centered()),
+                        (None, _) => Cell::new(Text::from("N/A").centered()).dark_gray().italic(),
+                    },
+                    match (p.get_remaining(), p.is_over_quota(warning_percent)) {
+                        (Some(remaining), true) => {
+                            Cell::new(Text::from(format_bytes(remaining)).centered())
+                                .light_red()
+                                .bold()
+                        }
+                        (Some(remaining), false) => {
+                            Cell::new(Text::from(format_bytes(remaining)).centered())
+                        }
+                        (None, _) => Cell::new(Text::from("N/A").centered()).dark_gray().italic(),
                     },
                     if let Some(timestamp) = p.expired_at {
                         Cell::new(
@@ -189,17 +554,35 @@ impl Profile {
                     } else {
                         Cell::new(Text::from("N/A").centered()).dark_gray().italic()
                     },
+                    match p.get_days_left(now) {
+                        Some(days) if days < 0 => Cell::new(Text::from("expired").centered())
+                            .light_red()
+                            .bold(),
+                        Some(days) if p.is_expiring(now, warning_days) => {
+                            Cell::new(Text::from(days.to_string()).centered())
+                                .light_yellow()
+                                .bold()
+                        }
+                        Some(days) => Cell::new(Text::from(days.to_string()).centered()),
+                        None => Cell::new(Text::from("N/A").centered()).dark_gray().italic(),
+                    },
                 ])
             })
             .collect::<Vec<Row>>();
 
         let fallback_profile = Row::new(vec![
-            Cell::new(Text::from("X").centered()).green().bold(),
+            if active_profile.is_none() {
+                Cell::new(Text::from("X").centered()).green().bold()
+            } else {
+                Cell::default()
+            },
             Cell::new("Fallback"),
             Cell::new(Text::from("builtin").centered()).light_red(),
             Cell::new(Text::from("N/A").centered()).dark_gray().italic(),
             Cell::new(Text::from("N/A").centered()).dark_gray().italic(),
             Cell::new(Text::from("N/A").centered()).dark_gray().italic(),
+            Cell::new(Text::from("N/A").centered()).dark_gray().italic(),
+            Cell::new(Text::from("N/A").centered()).dark_gray().italic(),
         ]);
         rows.insert(0, fallback_profile);
 
@@ -210,35 +593,252 @@ impl Profile {
                 Constraint::Min(4),
                 Constraint::Length(9),
                 Constraint::Length(12),
-                Constraint::Length(10),
+                Constraint::Length(8),
                 Constraint::Length(12),
+                Constraint::Length(12),
+                Constraint::Length(10),
             ],
         )
         .header(header)
         .row_highlight_style(Style::default().on_white().black())
     }
+}
 
-    fn create_log(&self, height: u16) -> Paragraph {
-        let logger = Logger::get_instance().lock().unwrap();
-        let lines = logger
-            .get_buffer()
-            .iter()
-            .map(|(log_level, text)| match log_level {
-                LogLevel::Trace => Line::from(text.clone()),
-                LogLevel::Debug => Line::from(text.clone()).blue(),
-                LogLevel::Info => Line::from(text.clone()).green(),
-                LogLevel::Warn => Line::from(text.clone()).yellow(),
-                LogLevel::Error => Line::from(text.clone()).red(),
+enum ProfileModal {
+    Form(Box<ProfileForm>),
+    ConfirmDelete(String),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ProfileFormField {
+    Name,
+    Type,
+    Url,
+    UserAgent,
+    UseSystemProxy,
+    UseMihomoProxy,
+    AllowInvalidCertificates,
+    UpdateInterval,
+    ImportFile,
+}
+
+impl ProfileFormField {
+    const fn label(&self) -> &'static str {
+        match self {
+            Self::Name => "Name",
+            Self::Type => "Type",
+            Self::Url => "URL",
+            Self::UserAgent => "User Agent",
+            Self::UseSystemProxy => "Use System Proxy",
+            Self::UseMihomoProxy => "Use Mihomo Proxy",
+            Self::AllowInvalidCertificates => "Allow Invalid Certs",
+            Self::UpdateInterval => "Update Interval (m)",
+            Self::ImportFile => "Import File",
+        }
+    }
+}
+
+#[derive(Clone)]
+struct ProfileForm {
+    uuid: Option<String>,
+    name: Input,
+    remote: bool,
+    url: Input,
+    user_agent: Input,
+    use_system_proxy: bool,
+    use_mihomo_proxy: bool,
+    allow_invalid_certificates: bool,
+    update_interval: Input,
+    import_file: Input,
+    selected: usize,
+    editing: Option<Input>,
+    error: Option<String>,
+}
+
+impl ProfileForm {
+    fn new(uuid: Option<String>) -> Self {
+        let profile = uuid
+            .as_deref()
+            .and_then(|uuid| find_profile(uuid).ok())
+            .unwrap_or_default();
+        let remote = profile.remote.clone().unwrap_or_default();
+
+        Self {
+            remote: uuid.is_none() || profile.remote.is_some(),
+            uuid,
+            name: Input::new(profile.name),
+            url: Input::new(remote.url),
+            user_agent: Input::new(remote.user_agent),
+            use_system_proxy: remote.use_system_proxy,
+            use_mihomo_proxy: remote.use_mihomo_proxy,
+            allow_invalid_certificates: remote.allow_invalid_certificates,
+            update_interval: Input::new(
+                remote
+                    .update_interval
+                    .map(|i| i.to_string())
+                    .unwrap_or_default(),
+            ),
+            import_file: Input::default(),
+            selected: 0,
+            editing: None,
+            error: None,
+        }
+    }
+
+    fn get_fields(&self) -> Vec<ProfileFormField> {
+        if self.remote {
+            vec![
+                ProfileFormField::Name,
+                ProfileFormField::Type,
+                ProfileFormField::Url,
+                ProfileFormField::UserAgent,
+                ProfileFormField::UseSystemProxy,
+                ProfileFormField::UseMihomoProxy,
+                ProfileFormField::AllowInvalidCertificates,
+                ProfileFormField::UpdateInterval,
+            ]
+        } else {
+            vec![
+                ProfileFormField::Name,
+                ProfileFormField::Type,
+                ProfileFormField::ImportFile,
+            ]
+        }
+    }
+
+    fn get_input_mut(&mut self, field: ProfileFormField) -> Option<&mut Input> {
+        match field {
+            ProfileFormField::Name => Some(&mut self.name),
+            ProfileFormField::Url => Some(&mut self.url),
+            ProfileFormField::UserAgent => Some(&mut self.user_agent),
+            ProfileFormField::UpdateInterval => Some(&mut self.update_interval),
+            ProfileFormField::ImportFile => Some(&mut self.import_file),
+            _ => None,
+        }
+    }
+
+    /// Handles navigation and editing keys, returns `false` for form actions
+    fn handle_key(&mut self, key: &KeyEvent) -> bool {
+        let field = self.get_fields()[self.selected];
+
+        if let Some(backup) = self.editing.take() {
+            match key.code {
+                KeyCode::Enter => (),
+                KeyCode::Esc => *self.get_input_mut(field).unwrap() = backup,
+                _ => {
+                    self.get_input_mut(field).unwrap().handle_key(key);
+                    self.editing = Some(backup);
+                }
+            }
+
+            return true;
+        }
+
+        match key.code {
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(self.get_fields().len() - 1),
+            KeyCode::Enter => match field {
+                ProfileFormField::Type => self.remote = !self.remote,
+                ProfileFormField::UseSystemProxy => self.use_system_proxy = !self.use_system_proxy,
+                ProfileFormField::UseMihomoProxy => self.use_mihomo_proxy = !self.use_mihomo_proxy,
+                ProfileFormField::AllowInvalidCertificates => {
+                    self.allow_invalid_certificates = !self.allow_invalid_certificates
+                }
+                _ => self.editing = self.get_input_mut(field).cloned(),
+            },
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn create_form(&self) -> Paragraph<'_> {
+        let toggle = |b: bool| {
+            if b {
+                Line::from("Yes").light_green()
+            } else {
+                Line::from("No").dark_gray()
+            }
+        };
+
+        let mut lines = self
+            .get_fields()
+            .into_iter()
+            .enumerate()
+            .map(|(i, field)| {
+                let focused = i == self.selected && self.editing.is_some();
+                let value = match field {
+                    ProfileFormField::Name => self.name.create_line(focused),
+                    ProfileFormField::Type => {
+                        if self.remote {
+                            Line::from("remote").light_green()
+                        } else {
+                            Line::from("local").light_yellow()
+                        }
+                    }
+                    ProfileFormField::Url => self.url.create_line(focused),
+                    ProfileFormField::UserAgent => self.user_agent.create_line(focused),
+                    ProfileFormField::UseSystemProxy => toggle(self.use_system_proxy),
+                    ProfileFormField::UseMihomoProxy => toggle(self.use_mihomo_proxy),
+                    ProfileFormField::AllowInvalidCertificates => {
+                        toggle(self.allow_invalid_certificates)
+                    }
+                    ProfileFormField::UpdateInterval => {
+                        if !focused && self.update_interval.value().is_empty() {
+                            Line::from("Never").dark_gray().italic()
+                        } else {
+                            self.update_interval.create_line(focused)
+                        }
+                    }
+                    ProfileFormField::ImportFile => {
+                        if !focused && self.import_file.value().is_empty() {
+                            Line::from("N/A").dark_gray().italic()
+                        } else {
+                            self.import_file.create_line(focused)
+                        }
+                    }
+                };
+
+                let mut spans = vec![Span::from(format!(" {:<20}", field.label())).bold()];
+                spans.extend(value.spans.into_iter().map(|s| s.patch_style(value.style)));
+
+                if i == self.selected && self.editing.is_none() {
+                    Line::from(spans).style(Style::default().on_white().black())
+                } else {
+                    Line::from(spans)
+                }
             })
             .collect::<Vec<Line>>();
-        let line_len = lines.len() as u16;
+
+        lines.push(Line::default());
+        match &self.error {
+            Some(err) => lines.push(Line::from(format!(" {}", err)).red()),
+            None => lines.push(
+                Line::from(" [ENTER]Edit/Toggle  [S]Save  [ESC]Cancel")
+                    .dark_gray()
+                    .italic(),
+            ),
+        }
 
         Paragraph::new(lines)
             .block(
                 Block::bordered()
                     .border_type(BorderType::Double)
-                    .title(" Log "),
+                    .title(match self.uuid {
+                        Some(_) => " Edit Profile ",
+                        None => " Add Profile ",
+                    }),
             )
-            .scroll((line_len.checked_sub(height).or(Some(0)).unwrap(), 0))
+            .wrap(Wrap { trim: false })
     }
 }
+
+fn find_profile(uuid: &str) -> Result<profile::Profile> {
+    ProfileManager::get_all()
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|p| p.uuid == uuid)
+        .cloned()
+        .ok_or_else(|| anyhow!("profile `{}` no longer exists", uuid))
+}
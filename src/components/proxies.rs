@@ -0,0 +1,426 @@
+use std::{cell::RefCell, collections::BTreeMap};
+
+use anyhow::{Context, Result};
+use crossterm::event::{Event, KeyCode, KeyEventKind};
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Style, Stylize},
+    text::Text,
+    widgets::{Block, BorderType, Cell, Row, Table, TableState},
+    Frame,
+};
+
+use crate::{
+    app::App,
+    config::tui::TuiConfig,
+    task::{TaskEvent, TaskManager},
+    utils::{
+        api::models::{Proxy, ProxyType},
+        logger::Logger,
+    },
+};
+
+use super::{create_log, Component};
+
+const TICKS_PER_REFRESH: u64 = 25;
+const DELAY_TEST_URL: &str = "https://www.gstatic.com/generate_204";
+const DELAY_TEST_TIMEOUT: u64 = 5_000;
+
+pub struct Proxies {
+    ticks: u64,
+    refreshing: Option<u64>,
+    actions: Vec<u64>,
+    groups: Vec<Proxy>,
+    proxies: BTreeMap<String, Proxy>,
+    focus: ProxiesFocus,
+    group_state: RefCell<TableState>,
+    member_state: RefCell<TableState>,
+}
+
+impl Component for Proxies {
+    fn new() -> Self {
+        *App::get_instance().help_text.lock().unwrap() =
+            "[ESC]Quit  [UP/DOWN]Move cursor  [LEFT/RIGHT]Switch pane  [ENTER]Select  [T]Test group  [P]Test proxy  [R]Refresh".into();
+
+        Self {
+            ticks: 0,
+            refreshing: None,
+            actions: Vec::new(),
+            groups: Vec::new(),
+            proxies: BTreeMap::new(),
+            focus: ProxiesFocus::Groups,
+            group_state: RefCell::new(TableState::new().with_selected(0)),
+            member_state: RefCell::new(TableState::new().with_selected(0)),
+        }
+    }
+
+    fn render(&self, area: &Rect, frame: &mut Frame) {
+        let [main_area, log_area] =
+            Layout::vertical(vec![Constraint::Ratio(3, 4), Constraint::Ratio(1, 4)]).areas(*area);
+        let [group_area, member_area] =
+            Layout::horizontal(vec![Constraint::Ratio(1, 3), Constraint::Ratio(2, 3)])
+                .areas(main_area);
+
+        frame.render_stateful_widget(
+            self.create_group_table(),
+            group_area,
+            &mut self.group_state.borrow_mut(),
+        );
+        frame.render_stateful_widget(
+            self.create_member_table(),
+            member_area,
+            &mut self.member_state.borrow_mut(),
+        );
+        frame.render_widget(create_log(log_area.height), log_area);
+    }
+
+    async fn tick(&mut self) -> Result<()> {
+        if self.ticks.is_multiple_of(TICKS_PER_REFRESH) {
+            self.refresh();
+        }
+        self.ticks += 1;
+
+        Ok(())
+    }
+
+    async fn handle_event(&mut self, ev: &Event) -> Result<()> {
+        let Event::Key(key) = ev else {
+            return Ok(());
+        };
+        if key.kind != KeyEventKind::Press {
+            return Ok(());
+        }
+
+        match key.code {
+            KeyCode::Left => self.focus = ProxiesFocus::Groups,
+            KeyCode::Right => self.focus = ProxiesFocus::Members,
+            KeyCode::Up => self.move_cursor(false),
+            KeyCode::Down => self.move_cursor(true),
+            KeyCode::Enter => self.select_member(),
+            KeyCode::Char('t') | KeyCode::Char('T') => self.test_group(),
+            KeyCode::Char('p') | KeyCode::Char('P') => self.test_member(),
+            KeyCode::Char('r') | KeyCode::Char('R') => self.refresh(),
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    async fn handle_task(&mut self, ev: &TaskEvent) -> Result<()> {
+        if self.refreshing == Some(ev.id) {
+            self.refreshing = None;
+
+            match ev.output::<(Vec<Proxy>, BTreeMap<String, Proxy>)>() {
+                Ok((groups, proxies)) => self.apply(groups.clone(), proxies.clone()),
+                Err(err) => Logger::get_instance()
+                    .lock()
+                    .unwrap()
+                    .error(format!("could not fetch proxies: {:#}", err)),
+            }
+        } else if let Some(index) = self.actions.iter().position(|id| *id == ev.id) {
+            self.actions.remove(index);
+
+            if let Err(err) = &ev.result {
+                Logger::get_instance()
+                    .lock()
+                    .unwrap()
+                    .error(format!("{:#}", err));
+            }
+            self.refresh();
+        }
+
+        Ok(())
+    }
+}
+
+impl Proxies {
+    fn refresh(&mut self) {
+        if self.refreshing.is_some() {
+            return;
+        }
+
+        self.refreshing = Some(TaskManager::get_instance().spawn("Fetch proxies", async {
+            let api = TuiConfig::global().lock().unwrap().get_mihomo_api();
+            let groups = api.get_groups().await?;
+            let proxies = api.get_proxies().await?;
+
+            Ok((groups, proxies))
+        }));
+    }
+
+    fn apply(&mut self, groups: Vec<Proxy>, proxies: BTreeMap<String, Proxy>) {
+        self.groups = groups;
+        self.proxies = proxies;
+
+        // Keep the cursors inside the new bounds
+        let mut state = self.group_state.borrow_mut();
+        if state.selected().unwrap_or(0) >= self.groups.len() {
+            state.select(Some(self.groups.len().saturating_sub(1)));
+        }
+        drop(state);
+
+        let total = self.get_members().len();
+        let mut state = self.member_state.borrow_mut();
+        if state.selected().unwrap_or(0) >= total {
+            state.select(Some(total.saturating_sub(1)));
+        }
+    }
+
+    fn select_member(&mut self) {
+        if self.focus != ProxiesFocus::Members {
+            self.focus = ProxiesFocus::Members;
+            return;
+        }
+
+        let (Some(group), Some(member)) = (self.get_group(), self.get_member()) else {
+            return;
+        };
+        let group_name = group.name.clone();
+
+        if group.proxy_type != ProxyType::Selector {
+            Logger::get_instance().lock().unwrap().warn(format!(
+                "Group \"{}\" is not a selector, its selection could not be changed",
+                group_name
+            ));
+            return;
+        }
+
+        Logger::get_instance().lock().unwrap().info(format!(
+            "Switching group \"{}\" to \"{}\"",
+            group_name, member
+        ));
+
+        self.actions.push(TaskManager::get_instance().spawn(
+            format!("Switch group \"{}\"", group_name),
+            async move {
+                let api = TuiConfig::global().lock().unwrap().get_mihomo_api();
+                Ok(api.update_proxy(&group_name, &member).await?)
+            },
+        ));
+    }
+
+    fn test_group(&mut self) {
+        let Some(group) = self.get_group() else {
+            return;
+        };
+        let group_name = group.name.clone();
+
+        Logger::get_instance()
+            .lock()
+            .unwrap()
+            .info(format!("Testing delay of group \"{}\"", group_name));
+
+        self.actions.push(TaskManager::get_instance().spawn(
+            format!("Test group \"{}\"", group_name),
+            async move {
+                let api = TuiConfig::global().lock().unwrap().get_mihomo_api();
+                api.test_group_delay(&group_name, DELAY_TEST_URL, DELAY_TEST_TIMEOUT)
+                    .await?;
+
+                Ok(())
+            },
+        ));
+    }
+
+    fn test_member(&mut self) {
+        let Some(member) = self.get_member() else {
+            return;
+        };
+        // Proxies of a provider are tested through it so the provider records the result
+        let provider = self
+            .proxies
+            .get(&member)
+            .map(|p| p.provider_name.clone())
+            .filter(|p| !p.is_empty() && p != "default");
+
+        self.actions.push(TaskManager::get_instance().spawn(
+            format!("Test proxy \"{}\"", member),
+            async move {
+                let api = TuiConfig::global().lock().unwrap().get_mihomo_api();
+                let delay = match provider {
+                    Some(provider) => {
+                        api.test_provider_proxy_delay(
+                            &provider,
+                            &member,
+                            DELAY_TEST_URL,
+                            DELAY_TEST_TIMEOUT,
+                        )
+                        .await
+                    }
+                    None => {
+                        api.test_proxy_delay(&member, DELAY_TEST_URL, DELAY_TEST_TIMEOUT)
+                            .await
+                    }
+                }
+                .with_context(|| format!("delay test of proxy \"{}\" failed", member))?;
+
+                Logger::get_instance()
+                    .lock()
+                    .unwrap()
+                    .info(format!("Delay of proxy \"{}\" is {}ms", member, delay));
+
+                Ok(())
+            },
+        ));
+    }
+
+    fn move_cursor(&mut self, down: bool) {
+        let (state, total) = match self.focus {
+            ProxiesFocus::Groups => (&self.group_state, self.groups.len()),
+            ProxiesFocus::Members => (&self.member_state, self.get_members().len()),
+        };
+        let mut state = state.borrow_mut();
+        let selected = state.selected().unwrap_or(0);
+
+        if down && selected + 1 < total {
+            state.select(Some(selected + 1));
+        } else if !down && selected > 0 {
+            state.select(Some(selected - 1));
+        }
+
+        // Members differ between groups, so restart from the top
+        if self.focus == ProxiesFocus::Groups {
+            self.member_state.borrow_mut().select(Some(0));
+        }
+    }
+
+    fn get_group(&self) -> Option<&Proxy> {
+        self.groups
+            .get(self.group_state.borrow().selected().unwrap_or(0))
+    }
+
+    fn get_members(&self) -> Vec<String> {
+        self.get_group().map(|g| g.all.clone()).unwrap_or_default()
+    }
+
+    fn get_member(&self) -> Option<String> {
+        self.get_members()
+            .get(self.member_state.borrow().selected().unwrap_or(0))
+            .cloned()
+    }
+
+    fn get_last_delay(&self, name: &str) -> Option<u64> {
+        self.proxies.get(name).and_then(|p| p.last_delay())
+    }
+
+    fn create_group_table(&self) -> Table<'_> {
+        let header = Row::new(
+            ["Name", "Type", "Now"]
+                .into_iter()
+                .map(|s| Cell::new(Text::from(s).centered()).on_blue())
+                .collect::<Vec<Cell>>(),
+        )
+        .on_light_blue()
+        .white()
+        .bold();
+
+        let rows = self
+            .groups
+            .iter()
+            .map(|g| {
+                Row::new(vec![
+                    Cell::new(g.name.clone()),
+                    Cell::new(Text::from(g.proxy_type.as_str()).centered()).light_yellow(),
+                    Cell::new(g.now.clone()).light_green(),
+                ])
+            })
+            .collect::<Vec<Row>>();
+
+        Table::new(
+            rows,
+            vec![
+                Constraint::Min(4),
+                Constraint::Length(12),
+                Constraint::Min(4),
+            ],
+        )
+        .header(header)
+        .block(self.create_block(" Groups ", ProxiesFocus::Groups))
+        .row_highlight_style(Style::default().on_white().black())
+    }
+
+    fn create_member_table(&self) -> Table<'_> {
+        let header = Row::new(
+            ["Now", "Name", "Type", "UDP", "Delay"]
+                .into_iter()
+                .map(|s| Cell::new(Text::from(s).centered()).on_blue())
+                .collect::<Vec<Cell>>(),
+        )
+        .on_light_blue()
+        .white()
+        .bold();
+
+        let now = self.get_group().map(|g| g.now.as_str()).unwrap_or("");
+        let rows = self
+            .get_members()
+            .into_iter()
+            .map(|name| {
+                let proxy = self.proxies.get(&name);
+                let udp = proxy.map(|p| p.udp).unwrap_or(false);
+
+                Row::new(vec![
+                    if name == now {
+                        Cell::new(Text::from("X").centered()).green().bold()
+                    } else {
+                        Cell::default()
+                    },
+                    Cell::new(name.clone()),
+                    Cell::new(
+                        Text::from(proxy.map(|p| p.proxy_type.as_str()).unwrap_or("")).centered(),
+                    )
+                    .light_yellow(),
+                    if udp {
+                        Cell::new(Text::from("Yes").centered()).light_green()
+                    } else {
+                        Cell::new(Text::from("No").centered()).dark_gray()
+                    },
+                    match self.get_last_delay(&name) {
+                        None => Cell::new(Text::from("N/A").centered()).dark_gray().italic(),
+                        Some(0) => Cell::new(Text::from("Timeout").centered()).red(),
+                        Some(delay) if delay < 200 => {
+                            Cell::new(Text::from(format!("{}ms", delay)).centered()).light_green()
+                        }
+                        Some(delay) if delay < 500 => {
+                            Cell::new(Text::from(format!("{}ms", delay)).centered()).light_yellow()
+                        }
+                        Some(delay) => {
+                            Cell::new(Text::from(format!("{}ms", delay)).centered()).light_red()
+                        }
+                    },
+                ])
+            })
+            .collect::<Vec<Row>>();
+
+        Table::new(
+            rows,
+            vec![
+                Constraint::Length(5),
+                Constraint::Min(4),
+                Constraint::Length(14),
+                Constraint::Length(5),
+                Constraint::Length(9),
+            ],
+        )
+        .header(header)
+        .block(self.create_block(" Proxies ", ProxiesFocus::Members))
+        .row_highlight_style(Style::default().on_white().black())
+    }
+
+    fn create_block(&self, title: &'static str, focus: ProxiesFocus) -> Block<'static> {
+        let block = Block::bordered()
+            .border_type(BorderType::Double)
+            .title(title);
+
+        if self.focus == focus {
+            block.border_style(Style::default().light_yellow())
+        } else {
+            block.border_style(Style::default().dark_gray())
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ProxiesFocus {
+    Groups,
+    Members,
+}
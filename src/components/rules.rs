@@ -0,0 +1,358 @@
+use std::{cell::RefCell, collections::BTreeMap};
+
+use anyhow::Result;
+use chrono::DateTime;
+use crossterm::event::{Event, KeyCode, KeyEventKind};
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Style, Stylize},
+    text::Text,
+    widgets::{Block, BorderType, Cell, Paragraph, Row, Table, TableState},
+    Frame,
+};
+
+use crate::{
+    app::App,
+    config::tui::TuiConfig,
+    task::{TaskEvent, TaskManager},
+    utils::{
+        api::models::{Rule, RuleProvider},
+        logger::Logger,
+    },
+};
+
+use super::{create_log, input::Input, Component};
+
+pub struct Rules {
+    loaded: bool,
+    refreshing: Option<u64>,
+    actions: Vec<u64>,
+    rules: Vec<Rule>,
+    rule_sets: BTreeMap<String, RuleProvider>,
+    focus: RulesFocus,
+    search: Input,
+    searching: bool,
+    rule_state: RefCell<TableState>,
+    rule_set_state: RefCell<TableState>,
+}
+
+impl Component for Rules {
+    fn new() -> Self {
+        *App::get_instance().help_text.lock().unwrap() =
+            "[ESC]Quit  [UP/DOWN]Move cursor  [LEFT/RIGHT]Switch pane  [/]Search (ENTER keep, ESC clear)  [U]Update provider  [R]Refresh".into();
+
+        Self {
+            loaded: false,
+            refreshing: None,
+            actions: Vec::new(),
+            rules: Vec::new(),
+            rule_sets: BTreeMap::new(),
+            focus: RulesFocus::Rules,
+            search: Input::default(),
+            searching: false,
+            rule_state: RefCell::new(TableState::new().with_selected(0)),
+            rule_set_state: RefCell::new(TableState::new().with_selected(0)),
+        }
+    }
+
+    fn render(&self, area: &Rect, frame: &mut Frame) {
+        let [main_area, log_area] =
+            Layout::vertical(vec![Constraint::Ratio(3, 4), Constraint::Ratio(1, 4)]).areas(*area);
+        let [rule_area, rule_set_area] =
+            Layout::horizontal(vec![Constraint::Ratio(3, 5), Constraint::Ratio(2, 5)])
+                .areas(main_area);
+        let [search_area, rule_area] =
+            Layout::vertical(vec![Constraint::Length(3), Constraint::Min(0)]).areas(rule_area);
+
+        frame.render_widget(self.create_search(), search_area);
+        frame.render_stateful_widget(
+            self.create_rule_table(),
+            rule_area,
+            &mut self.rule_state.borrow_mut(),
+        );
+        frame.render_stateful_widget(
+            self.create_rule_set_table(),
+            rule_set_area,
+            &mut self.rule_set_state.borrow_mut(),
+        );
+        frame.render_widget(create_log(log_area.height), log_area);
+    }
+
+    async fn tick(&mut self) -> Result<()> {
+        if !self.loaded {
+            self.loaded = true;
+            self.refresh();
+        }
+
+        Ok(())
+    }
+
+    async fn handle_event(&mut self, ev: &Event) -> Result<()> {
+        let Event::Key(key) = ev else {
+            return Ok(());
+        };
+        if key.kind != KeyEventKind::Press {
+            return Ok(());
+        }
+
+        if self.searching {
+            match key.code {
+                KeyCode::Enter => self.searching = false,
+                KeyCode::Esc => {
+                    self.search.clear();
+                    self.searching = false;
+                }
+                _ => {
+                    if self.search.handle_key(key) {
+                        self.rule_state.borrow_mut().select(Some(0));
+                    }
+                }
+            }
+
+            return Ok(());
+        }
+
+        match key.code {
+            KeyCode::Left => self.focus = RulesFocus::Rules,
+            KeyCode::Right => self.focus = RulesFocus::RuleSets,
+            KeyCode::Up => self.move_cursor(false),
+            KeyCode::Down => self.move_cursor(true),
+            KeyCode::Char('/') => {
+                self.focus = RulesFocus::Rules;
+                self.searching = true;
+            }
+            KeyCode::Char('u') | KeyCode::Char('U') => self.update_rule_set(),
+            KeyCode::Char('r') | KeyCode::Char('R') => self.refresh(),
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    async fn handle_task(&mut self, ev: &TaskEvent) -> Result<()> {
+        if self.refreshing == Some(ev.id) {
+            self.refreshing = None;
+
+            match ev.output::<(Vec<Rule>, BTreeMap<String, RuleProvider>)>() {
+                Ok((rules, rule_sets)) => {
+                    self.rules = rules.clone();
+                    self.rule_sets = rule_sets.clone();
+                }
+                Err(err) => Logger::get_instance()
+                    .lock()
+                    .unwrap()
+                    .error(format!("could not fetch rules: {:#}", err)),
+            }
+        } else if let Some(index) = self.actions.iter().position(|id| *id == ev.id) {
+            self.actions.remove(index);
+
+            if let Err(err) = &ev.result {
+                Logger::get_instance()
+                    .lock()
+                    .unwrap()
+                    .error(format!("{:#}", err));
+            }
+            self.refresh();
+        }
+
+        Ok(())
+    }
+
+    fn is_editing(&self) -> bool {
+        self.searching
+    }
+}
+
+impl Rules {
+    fn refresh(&mut self) {
+        if self.refreshing.is_some() {
+            return;
+        }
+
+        self.refreshing = Some(TaskManager::get_instance().spawn("Fetch rules", async {
+            let api = TuiConfig::global().lock().unwrap().get_mihomo_api();
+            let rules = api.get_rules().await?;
+            let rule_sets = api.get_rule_sets().await?;
+
+            Ok((rules, rule_sets))
+        }));
+    }
+
+    fn update_rule_set(&mut self) {
+        let selected = self.rule_set_state.borrow().selected().unwrap_or(0);
+        let Some(name) = self.rule_sets.keys().nth(selected).cloned() else {
+            return;
+        };
+
+        Logger::get_instance()
+            .lock()
+            .unwrap()
+            .info(format!("Updating rule provider \"{}\"", name));
+
+        self.actions.push(TaskManager::get_instance().spawn(
+            format!("Update rule provider \"{}\"", name),
+            async move {
+                let api = TuiConfig::global().lock().unwrap().get_mihomo_api();
+                api.update_rule_set(&name).await?;
+
+                Logger::get_instance()
+                    .lock()
+                    .unwrap()
+                    .info(format!("Rule provider \"{}\" updated", name));
+
+                Ok(())
+            },
+        ));
+    }
+
+    fn move_cursor(&mut self, down: bool) {
+        let (state, total) = match self.focus {
+            RulesFocus::Rules => (&self.rule_state, self.get_filtered_rules().len()),
+            RulesFocus::RuleSets => (&self.rule_set_state, self.rule_sets.len()),
+        };
+        let mut state = state.borrow_mut();
+        let selected = state.selected().unwrap_or(0);
+
+        if down && selected + 1 < total {
+            state.select(Some(selected + 1));
+        } else if !down && selected > 0 {
+            state.select(Some(selected - 1));
+        }
+    }
+
+    fn get_filtered_rules(&self) -> Vec<&Rule> {
+        let keyword = self.search.value().to_lowercase();
+
+        self.rules
+            .iter()
+            .filter(|r| {
+                keyword.is_empty()
+                    || [&r.rule_type, &r.payload, &r.proxy]
+                        .iter()
+                        .any(|v| v.to_lowercase().contains(&keyword))
+            })
+            .collect()
+    }
+
+    fn create_search(&self) -> Paragraph<'_> {
+        let block = Block::bordered()
+            .border_type(BorderType::Double)
+            .title(" Search ");
+
+        if self.searching {
+            Paragraph::new(self.search.create_line(true))
+                .block(block.border_style(Style::default().light_yellow()))
+        } else if self.search.value().is_empty() {
+            Paragraph::new("Press [/] to filter rules")
+                .dark_gray()
+                .italic()
+                .block(block)
+        } else {
+            Paragraph::new(self.search.create_line(false)).block(block)
+        }
+    }
+
+    fn create_rule_table(&self) -> Table<'_> {
+        let header = Row::new(
+            ["Type", "Payload", "Proxy"]
+                .into_iter()
+                .map(|s| Cell::new(Text::from(s).centered()).on_blue())
+                .collect::<Vec<Cell>>(),
+        )
+        .on_light_blue()
+        .white()
+        .bold();
+
+        let rows = self
+            .get_filtered_rules()
+            .into_iter()
+            .map(|r| {
+                Row::new(vec![
+                    Cell::new(r.rule_type.clone()).light_yellow(),
+                    Cell::new(r.payload.clone()),
+                    Cell::new(r.proxy.clone()).light_green(),
+                ])
+            })
+            .collect::<Vec<Row>>();
+
+        Table::new(
+            rows,
+            vec![
+                Constraint::Length(16),
+                Constraint::Min(4),
+                Constraint::Length(16),
+            ],
+        )
+        .header(header)
+        .block(self.create_block(
+            format!(
+                " Rules ({}/{}) ",
+                self.get_filtered_rules().len(),
+                self.rules.len()
+            ),
+            RulesFocus::Rules,
+        ))
+        .row_highlight_style(Style::default().on_white().black())
+    }
+
+    fn create_rule_set_table(&self) -> Table<'_> {
+        let header = Row::new(
+            ["Name", "Behavior", "Rules", "Updated At"]
+                .into_iter()
+                .map(|s| Cell::new(Text::from(s).centered()).on_blue())
+                .collect::<Vec<Cell>>(),
+        )
+        .on_light_blue()
+        .white()
+        .bold();
+
+        let rows = self
+            .rule_sets
+            .values()
+            .map(|r| {
+                Row::new(vec![
+                    Cell::new(r.name.clone()),
+                    Cell::new(Text::from(r.behavior.clone()).centered()).light_yellow(),
+                    Cell::new(Text::from(r.rule_count.to_string()).centered()),
+                    match DateTime::parse_from_rfc3339(&r.updated_at) {
+                        Ok(time) => Cell::new(
+                            Text::from(time.format("%Y-%m-%d %H:%M").to_string()).centered(),
+                        ),
+                        Err(_) => Cell::new(Text::from("N/A").centered()).dark_gray().italic(),
+                    },
+                ])
+            })
+            .collect::<Vec<Row>>();
+
+        Table::new(
+            rows,
+            vec![
+                Constraint::Min(4),
+                Constraint::Length(10),
+                Constraint::Length(7),
+                Constraint::Length(18),
+            ],
+        )
+        .header(header)
+        .block(self.create_block(" Providers ".into(), RulesFocus::RuleSets))
+        .row_highlight_style(Style::default().on_white().black())
+    }
+
+    fn create_block(&self, title: String, focus: RulesFocus) -> Block<'static> {
+        let block = Block::bordered()
+            .border_type(BorderType::Double)
+            .title(title);
+
+        if self.focus == focus {
+            block.border_style(Style::default().light_yellow())
+        } else {
+            block.border_style(Style::default().dark_gray())
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RulesFocus {
+    Rules,
+    RuleSets,
+}
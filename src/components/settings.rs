@@ -0,0 +1,505 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use crossterm::event::{Event, KeyCode, KeyEventKind};
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Clear, Paragraph},
+    Frame,
+};
+
+use crate::{
+    app::App,
+    config::{
+        profile::ProfileManager,
+        tui::{Controller, TuiConfig, TuiConfigMode},
+    },
+    task::{TaskEvent, TaskManager},
+    utils::logger::Logger,
+};
+
+use super::{create_log, create_popup_area, input::Input, Component};
+
+/// Rows of the form, in display order
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Name,
+    Api,
+    Secret,
+    DataDir,
+    ConnectTimeout,
+    RequestTimeout,
+    Retries,
+    CaCert,
+    ClientCert,
+    ClientKey,
+    Fingerprint,
+    ExpiryWarning,
+    QuotaWarning,
+    CoreBinary,
+    RestartTimeout,
+    /// Toggled with enter, the only row without an input
+    Mode,
+}
+
+impl Field {
+    const ALL: [Self; 16] = [
+        Self::Name,
+        Self::Api,
+        Self::Secret,
+        Self::DataDir,
+        Self::ConnectTimeout,
+        Self::RequestTimeout,
+        Self::Retries,
+        Self::CaCert,
+        Self::ClientCert,
+        Self::ClientKey,
+        Self::Fingerprint,
+        Self::ExpiryWarning,
+        Self::QuotaWarning,
+        Self::CoreBinary,
+        Self::RestartTimeout,
+        Self::Mode,
+    ];
+
+    const fn label(self) -> &'static str {
+        match self {
+            Self::Name => "Controller Name",
+            Self::Api => "Controller API",
+            Self::Secret => "Controller Secret",
+            Self::DataDir => "Mihomo Data Dir",
+            Self::ConnectTimeout => "Connect Timeout (ms)",
+            Self::RequestTimeout => "Request Timeout (ms)",
+            Self::Retries => "GET Retries",
+            Self::CaCert => "TLS CA Cert",
+            Self::ClientCert => "TLS Client Cert",
+            Self::ClientKey => "TLS Client Key",
+            Self::Fingerprint => "TLS Fingerprint",
+            Self::ExpiryWarning => "Expiry Warning (days)",
+            Self::QuotaWarning => "Quota Warning (%)",
+            Self::CoreBinary => "Mihomo Core Binary",
+            Self::RestartTimeout => "Restart Timeout (ms)",
+            Self::Mode => "Mode",
+        }
+    }
+}
+
+pub struct Settings {
+    inputs: [Input; Field::Mode as usize],
+    /// Index of the edited controller, `None` for a new one
+    controller: Option<usize>,
+    mode: TuiConfigMode,
+    selected: usize,
+    editing: Option<Input>,
+    error: Option<String>,
+    reactivating: Option<u64>,
+    confirming_delete: bool,
+}
+
+impl Component for Settings {
+    fn new() -> Self {
+        *App::get_instance().help_text.lock().unwrap() =
+            "[ESC]Quit  [UP/DOWN]Move cursor  [ENTER]Edit  [S]Save  [R]Reset  [N]New controller  [D]Delete controller".into();
+
+        let mut settings = Self {
+            inputs: Default::default(),
+            controller: None,
+            mode: TuiConfigMode::Direct,
+            selected: 0,
+            editing: None,
+            error: None,
+            reactivating: None,
+            confirming_delete: false,
+        };
+        settings.reset();
+
+        settings
+    }
+
+    fn render(&self, area: &Rect, frame: &mut Frame) {
+        let [form_area, log_area] =
+            Layout::vertical(vec![Constraint::Min(0), Constraint::Ratio(1, 4)]).areas(*area);
+
+        frame.render_widget(self.create_form(form_area.height), form_area);
+        frame.render_widget(create_log(log_area.height), log_area);
+
+        if self.confirming_delete {
+            let popup_area = create_popup_area(area, 48, 5);
+            frame.render_widget(Clear, popup_area);
+            frame.render_widget(self.create_confirm_delete(), popup_area);
+        }
+    }
+
+    async fn tick(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn handle_event(&mut self, ev: &Event) -> Result<()> {
+        let Event::Key(key) = ev else {
+            return Ok(());
+        };
+        if key.kind != KeyEventKind::Press {
+            return Ok(());
+        }
+
+        if let Some(backup) = &self.editing {
+            match key.code {
+                KeyCode::Enter => self.editing = None,
+                KeyCode::Esc => {
+                    self.inputs[self.selected] = backup.clone();
+                    self.editing = None;
+                }
+                _ => {
+                    self.inputs[self.selected].handle_key(key);
+                }
+            }
+
+            return Ok(());
+        }
+
+        if self.confirming_delete {
+            match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    self.confirming_delete = false;
+                    self.delete_controller().await;
+                }
+                KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                    self.confirming_delete = false;
+                }
+                _ => (),
+            }
+
+            return Ok(());
+        }
+
+        match key.code {
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(Field::ALL.len() - 1),
+            KeyCode::Enter => {
+                if Field::ALL[self.selected] == Field::Mode {
+                    self.mode = self.mode.next();
+                } else {
+                    self.editing = Some(self.inputs[self.selected].clone());
+                }
+            }
+            KeyCode::Char('s') | KeyCode::Char('S') => self.save().await,
+            KeyCode::Char('r') | KeyCode::Char('R') => self.reset(),
+            KeyCode::Char('n') | KeyCode::Char('N') => self.new_controller(),
+            KeyCode::Char('d') | KeyCode::Char('D') => {
+                if self.controller.is_some() {
+                    self.confirming_delete = true;
+                } else {
+                    // Not saved yet, just drop the form
+                    self.reset();
+                }
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    async fn handle_task(&mut self, ev: &TaskEvent) -> Result<()> {
+        if self.reactivating != Some(ev.id) {
+            return Ok(());
+        }
+        self.reactivating = None;
+
+        if let Err(err) = &ev.result {
+            Logger::get_instance()
+                .lock()
+                .unwrap()
+                .error(format!("{:#}", err));
+        }
+
+        Ok(())
+    }
+
+    fn is_editing(&self) -> bool {
+        self.editing.is_some() || self.confirming_delete
+    }
+}
+
+impl Settings {
+    fn reset(&mut self) {
+        let config = TuiConfig::global().lock().unwrap().clone();
+        let controller = config.controller().clone();
+        self.controller = Some(config.controller_index());
+
+        self.set(Field::Name, controller.name);
+        self.set(Field::Api, controller.api);
+        self.set(Field::Secret, controller.secret.unwrap_or_default());
+        self.set(Field::DataDir, controller.data_dir.unwrap_or_default());
+        self.set(Field::CaCert, controller.ca_cert.unwrap_or_default());
+        self.set(
+            Field::ClientCert,
+            controller.client_cert.unwrap_or_default(),
+        );
+        self.set(Field::ClientKey, controller.client_key.unwrap_or_default());
+        self.set(
+            Field::Fingerprint,
+            controller.fingerprint.unwrap_or_default(),
+        );
+        self.set(
+            Field::ConnectTimeout,
+            config.controller_connect_timeout.to_string(),
+        );
+        self.set(
+            Field::RequestTimeout,
+            config.controller_request_timeout.to_string(),
+        );
+        self.set(Field::Retries, config.controller_retries.to_string());
+        self.set(Field::ExpiryWarning, config.expiry_warning_days.to_string());
+        self.set(
+            Field::QuotaWarning,
+            config.quota_warning_percent.to_string(),
+        );
+        self.set(Field::CoreBinary, config.mihomo_binary.unwrap_or_default());
+        self.set(
+            Field::RestartTimeout,
+            config.core_restart_timeout.to_string(),
+        );
+        self.mode = config.mode;
+        self.error = None;
+    }
+
+    async fn save(&mut self) {
+        let old_config = TuiConfig::global().lock().unwrap().clone();
+
+        let mut config = old_config.clone();
+        let controller = Controller {
+            name: self.get(Field::Name).trim().to_owned(),
+            api: self.get(Field::Api).trim().to_owned(),
+            secret: non_empty(self.get(Field::Secret)),
+            data_dir: non_empty(self.get(Field::DataDir)),
+            ca_cert: non_empty(self.get(Field::CaCert)),
+            client_cert: non_empty(self.get(Field::ClientCert)),
+            client_key: non_empty(self.get(Field::ClientKey)),
+            fingerprint: non_empty(self.get(Field::Fingerprint)),
+        };
+        config.active_controller = Some(controller.name.clone());
+        match self.controller {
+            Some(i) => config.controllers[i] = controller,
+            None => config.controllers.push(controller),
+        }
+        config.mihomo_binary = non_empty(self.get(Field::CoreBinary));
+        config.mode = self.mode;
+
+        let result = self
+            .parse_numbers(&mut config)
+            .and_then(|_| config.validate());
+        if let Err(err) = result {
+            self.error = Some(format!("{:#}", err));
+            Logger::get_instance()
+                .lock()
+                .unwrap()
+                .error(format!("{:#}", err));
+            return;
+        }
+        self.error = None;
+
+        if let Err(err) = config.flush().await {
+            Logger::get_instance()
+                .lock()
+                .unwrap()
+                .error(format!("could not save settings: {:#}", err));
+            return;
+        }
+        *TuiConfig::global().lock().unwrap() = config.clone();
+        self.controller = Some(config.controller_index());
+
+        Logger::get_instance()
+            .lock()
+            .unwrap()
+            .info("Settings saved");
+
+        // Mode is baked into the generated config, so regenerate it
+        if config.mode != old_config.mode {
+            Logger::get_instance()
+                .lock()
+                .unwrap()
+                .info("Mode changed, re-applying active profile");
+
+            self.reactivating = Some(TaskManager::get_instance().spawn(
+                "Re-apply active profile",
+                async {
+                    ProfileManager::reactivate()
+                        .await
+                        .with_context(|| "could not re-apply active profile")
+                },
+            ));
+        }
+    }
+
+    /// Starts a blank controller entry, added on save
+    fn new_controller(&mut self) {
+        let count = TuiConfig::global().lock().unwrap().controllers.len();
+        let controller = Controller {
+            name: format!("controller-{}", count + 1),
+            ..Default::default()
+        };
+
+        self.set(Field::Name, controller.name);
+        self.set(Field::Api, controller.api);
+        for field in [
+            Field::Secret,
+            Field::DataDir,
+            Field::CaCert,
+            Field::ClientCert,
+            Field::ClientKey,
+            Field::Fingerprint,
+        ] {
+            self.set(field, String::new());
+        }
+        self.controller = None;
+        self.selected = 0;
+        self.error = None;
+    }
+
+    async fn delete_controller(&mut self) {
+        let Some(index) = self.controller else {
+            return;
+        };
+
+        let config = {
+            let mut config = TuiConfig::global().lock().unwrap();
+            if config.controllers.len() == 1 {
+                drop(config);
+                Logger::get_instance()
+                    .lock()
+                    .unwrap()
+                    .warn("The last controller cannot be deleted");
+                return;
+            }
+
+            let removed = config.controllers.remove(index);
+            config.active_controller = config.controllers.first().map(|c| c.name.clone());
+            Logger::get_instance()
+                .lock()
+                .unwrap()
+                .info(format!("Controller `{}` deleted", removed.name));
+            config.clone()
+        };
+
+        if let Err(err) = config.flush().await {
+            Logger::get_instance()
+                .lock()
+                .unwrap()
+                .error(format!("could not save settings: {:#}", err));
+        }
+        self.reset();
+    }
+
+    fn create_confirm_delete(&self) -> Paragraph<'static> {
+        let name = self
+            .controller
+            .and_then(|i| {
+                let config = TuiConfig::global().lock().unwrap();
+                config.controllers.get(i).map(|c| c.name.clone())
+            })
+            .unwrap_or_default();
+
+        Paragraph::new(vec![
+            Line::from(format!(" Delete controller `{}`?", name)),
+            Line::default(),
+            Line::from(" [Y]Yes  [N]No").dark_gray().italic(),
+        ])
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Double)
+                .title(" Delete Controller ")
+                .border_style(Style::default().light_red()),
+        )
+    }
+
+    fn parse_numbers(&self, config: &mut TuiConfig) -> Result<()> {
+        config.controller_connect_timeout = self.parse_number(Field::ConnectTimeout)?;
+        config.controller_request_timeout = self.parse_number(Field::RequestTimeout)?;
+        config.controller_retries = self.parse_number(Field::Retries)?;
+        config.expiry_warning_days = self.parse_number(Field::ExpiryWarning)?;
+        config.quota_warning_percent = self.parse_number(Field::QuotaWarning)?;
+        config.core_restart_timeout = self.parse_number(Field::RestartTimeout)?;
+
+        Ok(())
+    }
+
+    fn parse_number<T>(&self, field: Field) -> Result<T>
+    where
+        T: FromStr,
+    {
+        self.get(field)
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("{} must be a non-negative number", field.label()))
+    }
+
+    fn get(&self, field: Field) -> &str {
+        self.inputs[field as usize].value()
+    }
+
+    fn set(&mut self, field: Field, value: String) {
+        self.inputs[field as usize] = Input::new(value);
+    }
+
+    /// Scrolls just enough to keep the cursor inside the borders
+    fn create_form(&self, height: u16) -> Paragraph<'_> {
+        let visible = height.saturating_sub(2) as usize;
+        let offset = (self.selected + 1).saturating_sub(visible);
+
+        let mut lines = Field::ALL
+            .iter()
+            .enumerate()
+            .map(|(i, &field)| {
+                let value = if field == Field::Mode {
+                    Line::from(self.mode.as_str()).light_yellow()
+                } else if i == self.selected && self.editing.is_some() {
+                    self.inputs[i].create_line(true)
+                } else if field == Field::Secret && !self.get(field).is_empty() {
+                    Line::from("*".repeat(self.get(field).chars().count()))
+                } else if self.get(field).is_empty() {
+                    Line::from("N/A").dark_gray().italic()
+                } else {
+                    self.inputs[i].create_line(false)
+                };
+
+                let mut spans = vec![Span::from(format!(" {:<22}", field.label())).bold()];
+                spans.extend(value.spans.into_iter().map(|s| s.patch_style(value.style)));
+
+                if i == self.selected && self.editing.is_none() {
+                    Line::from(spans).style(Style::default().on_white().black())
+                } else {
+                    Line::from(spans)
+                }
+            })
+            .collect::<Vec<Line>>();
+
+        if let Some(err) = &self.error {
+            lines.push(Line::default());
+            lines.push(Line::from(format!(" {}", err)).red());
+        }
+
+        let title = if self.controller.is_some() {
+            " Settings "
+        } else {
+            " Settings (new controller) "
+        };
+
+        Paragraph::new(lines).scroll((offset as u16, 0)).block(
+            Block::bordered()
+                .border_type(BorderType::Double)
+                .title(title),
+        )
+    }
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_owned())
+    }
+}
@@ -1,27 +1,268 @@
+use std::time::Instant;
+
 use anyhow::Result;
 use crossterm::event::Event;
-use ratatui::{layout::Rect, Frame};
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::Stylize,
+    text::{Line, Span},
+    widgets::{Block, BorderType, Paragraph, Sparkline},
+    Frame,
+};
 
-use crate::app::App;
+use crate::{
+    app::App,
+    config::{profile::ProfileManager, tui::TuiConfig},
+    task::{TaskEvent, TaskManager},
+    utils::{
+        format::{format_bytes, format_rate},
+        logger::Logger,
+    },
+};
 
 use super::Component;
 
-pub struct Status {}
+const TICKS_PER_POLL: u64 = 5;
+const HISTORY_SIZE: usize = 256;
+
+pub struct Status {
+    ticks: u64,
+    pending: Option<u64>,
+    version: Option<String>,
+    mode: Option<String>,
+    upload_total: u64,
+    download_total: u64,
+    upload_rate: u64,
+    download_rate: u64,
+    upload_history: Vec<u64>,
+    download_history: Vec<u64>,
+    memory: u64,
+    connections: usize,
+    last_polled_at: Option<Instant>,
+    error: Option<String>,
+}
 
 impl Component for Status {
     fn new() -> Self {
         *App::get_instance().help_text.lock().unwrap() = "[ESC]Quit".into();
 
-        Self {}
+        Self {
+            ticks: 0,
+            pending: None,
+            version: None,
+            mode: None,
+            upload_total: 0,
+            download_total: 0,
+            upload_rate: 0,
+            download_rate: 0,
+            upload_history: Vec::new(),
+            download_history: Vec::new(),
+            memory: 0,
+            connections: 0,
+            last_polled_at: None,
+            error: None,
+        }
     }
 
-    fn render(&self, area: &Rect,  frame: &mut Frame) {}
+    fn render(&self, area: &Rect, frame: &mut Frame) {
+        let [info_area, upload_area, download_area] = Layout::vertical(vec![
+            Constraint::Length(11),
+            Constraint::Ratio(1, 2),
+            Constraint::Ratio(1, 2),
+        ])
+        .areas(*area);
+
+        frame.render_widget(self.create_info(), info_area);
+        frame.render_widget(
+            self.create_sparkline(" Upload ", &self.upload_history, upload_area.width)
+                .light_cyan(),
+            upload_area,
+        );
+        frame.render_widget(
+            self.create_sparkline(" Download ", &self.download_history, download_area.width)
+                .light_green(),
+            download_area,
+        );
+    }
 
     async fn tick(&mut self) -> Result<()> {
+        if self.ticks.is_multiple_of(TICKS_PER_POLL) && self.pending.is_none() {
+            self.pending = Some(TaskManager::get_instance().spawn("Poll status", Self::poll()));
+        }
+        self.ticks += 1;
+
+        Ok(())
+    }
+
+    async fn handle_event(&mut self, _: &Event) -> Result<()> {
         Ok(())
     }
 
-    async fn handle_event(&mut self, ev: &Event) -> Result<()> {
+    async fn handle_task(&mut self, ev: &TaskEvent) -> Result<()> {
+        if self.pending != Some(ev.id) {
+            return Ok(());
+        }
+        self.pending = None;
+
+        match ev.output::<StatusSnapshot>() {
+            Ok(snapshot) => self.apply(snapshot),
+            Err(err) => {
+                let error = format!("{:#}", err);
+
+                // Polling repeats every few ticks, only log when the error changes
+                if self.error.as_ref() != Some(&error) {
+                    Logger::get_instance()
+                        .lock()
+                        .unwrap()
+                        .error(format!("could not reach controller: {}", error));
+                }
+                self.version = None;
+                self.error = Some(error);
+            }
+        }
+
         Ok(())
     }
 }
+
+impl Status {
+    async fn poll() -> Result<StatusSnapshot> {
+        let api = TuiConfig::global().lock().unwrap().get_mihomo_api();
+
+        let version = api.get_version().await?;
+        let mode = api.get_configs().await?.mode.as_str().to_owned();
+        let connections = api.get_connections().await?;
+
+        Ok(StatusSnapshot {
+            version,
+            mode: Some(mode),
+            upload_total: connections.upload_total,
+            download_total: connections.download_total,
+            memory: connections.memory,
+            connections: connections.connections.len(),
+        })
+    }
+
+    fn apply(&mut self, snapshot: &StatusSnapshot) {
+        self.version = Some(snapshot.version.clone());
+        self.mode = snapshot.mode.clone();
+
+        // Traffic totals are cumulative, so rates are derived from the delta
+        let upload_total = snapshot.upload_total;
+        let download_total = snapshot.download_total;
+        let now = Instant::now();
+
+        if let Some(last_polled_at) = self.last_polled_at {
+            let elapsed = now.duration_since(last_polled_at).as_secs_f64();
+
+            if elapsed > 0.0 {
+                self.upload_rate =
+                    (upload_total.saturating_sub(self.upload_total) as f64 / elapsed) as u64;
+                self.download_rate =
+                    (download_total.saturating_sub(self.download_total) as f64 / elapsed) as u64;
+
+                Self::push_history(&mut self.upload_history, self.upload_rate);
+                Self::push_history(&mut self.download_history, self.download_rate);
+            }
+        }
+
+        self.upload_total = upload_total;
+        self.download_total = download_total;
+        self.memory = snapshot.memory;
+        self.connections = snapshot.connections;
+        self.last_polled_at = Some(now);
+        self.error = None;
+    }
+
+    fn push_history(history: &mut Vec<u64>, value: u64) {
+        history.push(value);
+        if history.len() > HISTORY_SIZE {
+            history.drain(0..1);
+        }
+    }
+
+    fn create_info(&self) -> Paragraph<'_> {
+        let na = || Span::from("N/A").dark_gray().italic();
+        let label = |s: &'static str| Span::from(format!("{:<16}", s)).bold();
+
+        let lines = vec![
+            Line::from(vec![
+                label("Core"),
+                match (&self.version, &self.error) {
+                    (Some(version), _) => Span::from(version.clone()).light_green(),
+                    (None, Some(err)) => Span::from(format!("Disconnected ({})", err)).red(),
+                    (None, None) => Span::from("Connecting...").dark_gray(),
+                },
+            ]),
+            Line::from(vec![
+                label("Mode"),
+                match &self.mode {
+                    Some(mode) => Span::from(mode.clone()).light_yellow(),
+                    None => na(),
+                },
+            ]),
+            Line::from(vec![
+                label("Active Profile"),
+                match ProfileManager::get_active_name() {
+                    Some(name) => Span::from(name).light_green(),
+                    None => na(),
+                },
+            ]),
+            Line::from(vec![
+                label("Upload"),
+                Span::from(format_rate(self.upload_rate)).light_cyan(),
+            ]),
+            Line::from(vec![
+                label("Download"),
+                Span::from(format_rate(self.download_rate)).light_green(),
+            ]),
+            Line::from(vec![
+                label("Total Upload"),
+                Span::from(format_bytes(self.upload_total)),
+            ]),
+            Line::from(vec![
+                label("Total Download"),
+                Span::from(format_bytes(self.download_total)),
+            ]),
+            Line::from(vec![label("Memory"), Span::from(format_bytes(self.memory))]),
+            Line::from(vec![
+                label("Connections"),
+                Span::from(self.connections.to_string()),
+            ]),
+        ];
+
+        Paragraph::new(lines).block(
+            Block::bordered()
+                .border_type(BorderType::Double)
+                .title(" Status "),
+        )
+    }
+
+    fn create_sparkline<'a>(
+        &self,
+        title: &'a str,
+        history: &'a [u64],
+        width: u16,
+    ) -> Sparkline<'a> {
+        // Only the most recent samples fit into the block
+        let width = width.saturating_sub(2) as usize;
+        let start = history.len().saturating_sub(width);
+
+        Sparkline::default()
+            .block(
+                Block::bordered()
+                    .border_type(BorderType::Double)
+                    .title(title),
+            )
+            .data(&history[start..])
+    }
+}
+
+struct StatusSnapshot {
+    version: String,
+    mode: Option<String>,
+    upload_total: u64,
+    download_total: u64,
+    memory: u64,
+    connections: usize,
+}
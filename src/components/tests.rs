@@ -0,0 +1,383 @@
+use std::time::Duration;
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{backend::TestBackend, Terminal};
+
+use crate::{
+    config::{
+        profile::{Profile as ProfileConfig, ProfileManager, ProfileRemote, ProfileTraffics},
+        tui::{Controller, TuiConfig},
+    },
+    event::Event as AppEvent,
+    task::TaskManager,
+    utils::{
+        api::mock::MockController,
+        logger::{LogLevel, Logger},
+        testing::{lock_globals, task_events},
+    },
+};
+
+use super::{
+    connections::Connections, profiles::Profile, settings::Settings, status::Status, Component,
+    Root,
+};
+
+const WIDTH: u16 = 100;
+const HEIGHT: u16 = 30;
+
+/// Draws the component on a fresh terminal, the buffer debug output keeps styles
+fn render<C: Component>(component: &C) -> String {
+    render_sized(component, WIDTH, HEIGHT)
+}
+
+fn render_sized<C: Component>(component: &C, width: u16, height: u16) -> String {
+    let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+    terminal
+        .draw(|frame| component.render(&frame.area(), frame))
+        .unwrap();
+
+    format!("{:?}", terminal.backend().buffer())
+}
+
+async fn press<C: Component>(component: &mut C, code: KeyCode) {
+    let ev = Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+    component.handle_event(&ev).await.unwrap();
+}
+
+/// Hands task results to the component until every spawned task is done
+async fn settle<C: Component>(component: &mut C) {
+    let mut idle = 0;
+    while idle < 5 {
+        let ev = task_events().lock().unwrap().try_recv();
+        match ev {
+            Ok(AppEvent::Task(ev)) => {
+                component.handle_task(&ev).await.unwrap();
+                idle = 0;
+            }
+            Ok(_) => (),
+            Err(_) => {
+                if TaskManager::get_instance().get_tasks().is_empty() {
+                    idle += 1;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }
+    }
+}
+
+fn fixture_profiles() {
+    *ProfileManager::get_all().lock().unwrap() = vec![
+        ProfileConfig {
+            uuid: "local".into(),
+            name: "Home".into(),
+            ..Default::default()
+        },
+        ProfileConfig {
+            uuid: "remote".into(),
+            name: "Subscription".into(),
+            remote: Some(ProfileRemote {
+                url: "https://example.com/sub".into(),
+                user_agent: "clash.meta".into(),
+                use_system_proxy: false,
+                use_mihomo_proxy: false,
+                allow_invalid_certificates: false,
+                update_interval: Some(1440),
+            }),
+            updated_at: Some(1733011200),
+            expired_at: Some(1735689600),
+            traffics: Some(ProfileTraffics {
+                used: Some(268435456),
+                total: Some(1073741824),
+            }),
+            ..Default::default()
+        },
+        ProfileConfig {
+            uuid: "updating".into(),
+            name: "Work".into(),
+            remote: Some(ProfileRemote {
+                url: "https://example.org/work".into(),
+                user_agent: "clash.meta".into(),
+                use_system_proxy: true,
+                use_mihomo_proxy: false,
+                allow_invalid_certificates: false,
+                update_interval: None,
+            }),
+            updating: true,
+            ..Default::default()
+        },
+    ];
+    TuiConfig::global().lock().unwrap().active_profile = Some("remote".into());
+
+    Logger::get_instance().lock().unwrap().set_buffer(vec![
+        (
+            LogLevel::Info,
+            "[2024-12-01T000000] [ Info] Activating profile \"Subscription\"".into(),
+        ),
+        (
+            LogLevel::Warn,
+            "[2024-12-01T000001] [ Warn] Profile \"Work\" has no update interval".into(),
+        ),
+        (
+            LogLevel::Error,
+            "[2024-12-01T000002] [Error] could not reach controller: connection refused".into(),
+        ),
+    ]);
+}
+
+#[tokio::test]
+async fn status_connecting() {
+    let _guard = lock_globals().await;
+    let status = Status::new();
+
+    insta::assert_snapshot!("status_connecting", render(&status));
+}
+
+#[tokio::test]
+async fn status_polled() {
+    let _guard = lock_globals().await;
+    let mock = MockController::start().await;
+    TuiConfig::global().lock().unwrap().controllers[0].api = mock.url.clone();
+
+    let mut status = Status::new();
+    status.tick().await.unwrap();
+    settle(&mut status).await;
+
+    insta::assert_snapshot!("status_polled", render(&status));
+}
+
+#[tokio::test]
+async fn root_initial() {
+    let _guard = lock_globals().await;
+    let root = Root::new();
+
+    insta::assert_snapshot!("root_initial", render(&root));
+}
+
+#[tokio::test]
+async fn root_popups() {
+    let _guard = lock_globals().await;
+    let mut root = Root::new();
+
+    press(&mut root, KeyCode::F(9)).await;
+    insta::assert_snapshot!("root_controllers", render(&root));
+    press(&mut root, KeyCode::Esc).await;
+
+    press(&mut root, KeyCode::F(10)).await;
+    insta::assert_snapshot!("root_tasks", render(&root));
+}
+
+#[tokio::test]
+async fn root_switches_tabs() {
+    let _guard = lock_globals().await;
+    fixture_profiles();
+    let mut root = Root::new();
+
+    press(&mut root, KeyCode::F(2)).await;
+    insta::assert_snapshot!("root_profiles", render(&root));
+}
+
+#[tokio::test]
+async fn profiles_table() {
+    let _guard = lock_globals().await;
+    fixture_profiles();
+    let mut profiles = Profile::new();
+
+    insta::assert_snapshot!("profiles_table", render(&profiles));
+
+    press(&mut profiles, KeyCode::Down).await;
+    press(&mut profiles, KeyCode::Down).await;
+    insta::assert_snapshot!("profiles_cursor", render(&profiles));
+
+    // The cursor stops at the last row
+    press(&mut profiles, KeyCode::Down).await;
+    press(&mut profiles, KeyCode::Down).await;
+    insta::assert_snapshot!("profiles_cursor_last", render(&profiles));
+}
+
+#[tokio::test]
+async fn profiles_modals() {
+    let _guard = lock_globals().await;
+    fixture_profiles();
+    let mut profiles = Profile::new();
+
+    press(&mut profiles, KeyCode::Char('a')).await;
+    insta::assert_snapshot!("profiles_add_form", render(&profiles));
+    press(&mut profiles, KeyCode::Esc).await;
+
+    press(&mut profiles, KeyCode::Down).await;
+    press(&mut profiles, KeyCode::Char('d')).await;
+    insta::assert_snapshot!("profiles_confirm_delete", render(&profiles));
+}
+
+#[tokio::test]
+async fn connections_confirm_close_all() {
+    let _guard = lock_globals().await;
+    let mock = MockController::start().await;
+    TuiConfig::global().lock().unwrap().controllers[0].api = mock.url.clone();
+
+    let mut connections = Connections::new();
+    connections.tick().await.unwrap();
+    settle(&mut connections).await;
+
+    press(&mut connections, KeyCode::Char('c')).await;
+    insta::assert_snapshot!("connections_confirm_close", render(&connections));
+
+    press(&mut connections, KeyCode::Esc).await;
+    settle(&mut connections).await;
+    assert_eq!(mock.state().connections.len(), 2);
+
+    press(&mut connections, KeyCode::Char('c')).await;
+    press(&mut connections, KeyCode::Char('y')).await;
+    settle(&mut connections).await;
+    assert!(mock.state().connections.is_empty());
+}
+
+#[tokio::test]
+async fn profiles_refuse_deleting_active_or_updating() {
+    let _guard = lock_globals().await;
+    fixture_profiles();
+    let mut profiles = Profile::new();
+
+    // "Subscription" is active, "Work" is updating
+    for _ in 0..2 {
+        press(&mut profiles, KeyCode::Down).await;
+    }
+    press(&mut profiles, KeyCode::Char('d')).await;
+    assert!(!profiles.is_editing());
+
+    press(&mut profiles, KeyCode::Down).await;
+    press(&mut profiles, KeyCode::Char('d')).await;
+    assert!(!profiles.is_editing());
+
+    assert_eq!(ProfileManager::get_all().lock().unwrap().len(), 3);
+    let warnings = Logger::get_instance()
+        .lock()
+        .unwrap()
+        .get_buffer()
+        .iter()
+        .filter(|(level, _)| matches!(level, LogLevel::Warn))
+        .count();
+    assert_eq!(warnings, 3);
+}
+
+#[tokio::test]
+async fn profiles_save_form_by_uuid() {
+    let _guard = lock_globals().await;
+    fixture_profiles();
+    let mut profiles = Profile::new();
+
+    // Edit "Subscription", while "Home" is removed and an update lands meanwhile
+    for _ in 0..2 {
+        press(&mut profiles, KeyCode::Down).await;
+    }
+    press(&mut profiles, KeyCode::Char('e')).await;
+    press(&mut profiles, KeyCode::Enter).await;
+    press(&mut profiles, KeyCode::Char('!')).await;
+    press(&mut profiles, KeyCode::Enter).await;
+    {
+        let mut all = ProfileManager::get_all().lock().unwrap();
+        all.remove(0);
+        all[0].updated_at = Some(1735000000);
+    }
+    press(&mut profiles, KeyCode::Char('s')).await;
+    assert!(!profiles.is_editing());
+
+    let all = ProfileManager::get_all().lock().unwrap();
+    assert_eq!(all[0].uuid, "remote");
+    assert_eq!(all[0].name, "Subscription!");
+    assert_eq!(all[0].updated_at, Some(1735000000));
+    assert_eq!(all[1].name, "Work");
+}
+
+#[tokio::test]
+async fn profiles_update_all_needs_shift() {
+    let _guard = lock_globals().await;
+    fixture_profiles();
+    ProfileManager::get_all().lock().unwrap().truncate(1);
+    let mut profiles = Profile::new();
+    press(&mut profiles, KeyCode::Down).await;
+
+    // Caps lock sends an uppercase `U` without SHIFT
+    press(&mut profiles, KeyCode::Char('U')).await;
+    let ev = Event::Key(KeyEvent::new(KeyCode::Char('U'), KeyModifiers::SHIFT));
+    profiles.handle_event(&ev).await.unwrap();
+    settle(&mut profiles).await;
+
+    let logs = Logger::get_instance()
+        .lock()
+        .unwrap()
+        .get_buffer()
+        .iter()
+        .skip(3)
+        .map(|(_, line)| line.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(logs.len(), 2);
+    assert!(logs[0].ends_with("Profile \"Home\" is local and could not be updated"));
+    assert!(logs[1].ends_with("Updating all remote profiles"));
+}
+
+#[tokio::test]
+async fn settings_confirm_controller_delete() {
+    let _guard = lock_globals().await;
+    TuiConfig::global()
+        .lock()
+        .unwrap()
+        .controllers
+        .push(Controller {
+            name: "router".into(),
+            ..Default::default()
+        });
+    let mut settings = Settings::new();
+
+    press(&mut settings, KeyCode::Char('d')).await;
+    assert!(settings.is_editing());
+    press(&mut settings, KeyCode::Esc).await;
+    assert!(!settings.is_editing());
+    assert_eq!(TuiConfig::global().lock().unwrap().controllers.len(), 2);
+
+    press(&mut settings, KeyCode::Char('d')).await;
+    press(&mut settings, KeyCode::Char('y')).await;
+    let config = TuiConfig::global().lock().unwrap().clone();
+    assert_eq!(config.controllers.len(), 1);
+    assert_eq!(config.controllers[0].name, "router");
+}
+
+#[tokio::test]
+async fn settings_save_named_fields() {
+    let _guard = lock_globals().await;
+    let mut settings = Settings::new();
+
+    // "Restart Timeout (ms)" is the row above "Mode"
+    for _ in 0..14 {
+        press(&mut settings, KeyCode::Down).await;
+    }
+    press(&mut settings, KeyCode::Enter).await;
+    for _ in 0..5 {
+        press(&mut settings, KeyCode::Backspace).await;
+    }
+    for c in "2500".chars() {
+        press(&mut settings, KeyCode::Char(c)).await;
+    }
+    press(&mut settings, KeyCode::Enter).await;
+    press(&mut settings, KeyCode::Char('s')).await;
+
+    let config = TuiConfig::global().lock().unwrap().clone();
+    assert_eq!(config.core_restart_timeout, 2500);
+    assert_eq!(
+        config.controller_connect_timeout,
+        TuiConfig::default().controller_connect_timeout
+    );
+}
+
+#[tokio::test]
+async fn settings_scroll_to_cursor() {
+    let _guard = lock_globals().await;
+    let mut root = Root::new();
+    press(&mut root, KeyCode::F(5)).await;
+
+    // The last rows do not fit on a small terminal without scrolling
+    for _ in 0..15 {
+        press(&mut root, KeyCode::Down).await;
+    }
+    insta::assert_snapshot!("settings_scrolled", render_sized(&root, 80, 24));
+}
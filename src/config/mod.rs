@@ -1,2 +1,34 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use tokio::{
+    fs::{self, File},
+    io::AsyncWriteExt,
+};
+use uuid::Uuid;
+
 pub mod profile;
 pub mod tui;
+pub mod validation;
+
+/// Writes to a sibling file first so readers never see a partial file
+pub(crate) async fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(".{}.tmp", Uuid::new_v4()));
+    let tmp = PathBuf::from(tmp);
+
+    let mut file = File::create(&tmp)
+        .await
+        .with_context(|| format!("could not create file `{}`", tmp.display()))?;
+    file.write_all(contents.as_bytes())
+        .await
+        .with_context(|| format!("could not write buffer for file `{}`", tmp.display()))?;
+    file.sync_all()
+        .await
+        .with_context(|| format!("could not flush buffer for file `{}`", tmp.display()))?;
+    fs::rename(&tmp, path)
+        .await
+        .with_context(|| format!("could not replace file `{}`", path.display()))?;
+
+    Ok(())
+}
@@ -6,8 +6,9 @@ use std::{
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use boa_engine::{js_string, property::Attribute, Source};
+use futures::future;
 use reqwest::{ClientBuilder, Proxy};
 use serde::{Deserialize, Serialize};
 use serde_yaml::Value;
@@ -18,11 +19,23 @@ use tokio::{
 use uuid::Uuid;
 
 use crate::utils::{
+    format::format_bytes,
+    logger::Logger,
     path::{get_data_dir, get_profiles_dir},
     script::create_context,
+    subscription,
 };
 
-use super::tui::TuiConfig;
+use super::{
+    tui::TuiConfig,
+    validation::{self, Severity},
+    write_atomic,
+};
+
+/// Delay before a failed scheduled update is retried, capped by the interval
+const SCHEDULE_RETRY_SECS: u64 = 10 * 60;
+/// Delay between version checks while waiting for a restarted core
+const RESTART_POLL_INTERVAL: Duration = Duration::from_millis(250);
 
 pub struct ProfileManager {}
 
@@ -45,20 +58,119 @@ impl ProfileManager {
         let profiles = Self::get_all().lock().unwrap().clone();
 
         let path = get_data_dir().join("profiles.yaml");
-        let mut file = File::create(path).await?;
-        file.write_all(serde_yaml::to_string(&profiles)?.as_bytes())
-            .await?;
-        file.flush().await?;
+        write_atomic(&path, &serde_yaml::to_string(&profiles)?).await
+    }
 
-        Ok(())
+    pub async fn update(uuid: &str) -> Result<()> {
+        Self::fetch(uuid).await?;
+        Self::flush_all().await
+    }
+
+    pub async fn update_all() -> Result<Vec<(String, Result<()>)>> {
+        let targets = Self::get_all()
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|p| p.remote.is_some())
+            .map(|p| (p.uuid.clone(), p.name.clone()))
+            .collect::<Vec<(String, String)>>();
+
+        let results = future::join_all(targets.iter().map(|(uuid, _)| Self::fetch(uuid))).await;
+        Self::flush_all().await?;
+
+        Ok(targets
+            .into_iter()
+            .map(|(_, name)| name)
+            .zip(results)
+            .collect())
     }
 
-    pub async fn update_all() -> Result<()> {
-        for profile in Self::get_all().lock().unwrap().iter_mut() {
-            let _ = profile.update().await;
+    /// Updates remote profiles whose interval elapsed, re-activating the active one
+    pub async fn update_due() -> Result<Vec<(String, Result<()>)>> {
+        let now = unix_now();
+        let targets = Self::get_all()
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .filter(|p| p.is_due(now))
+            .map(|p| {
+                p.scheduled_at = Some(now);
+                (p.uuid.clone(), p.name.clone())
+            })
+            .collect::<Vec<(String, String)>>();
+        if targets.is_empty() {
+            return Ok(Vec::new());
         }
 
-        Ok(())
+        let results = future::join_all(targets.iter().map(|(uuid, _)| Self::fetch(uuid))).await;
+        Self::flush_all().await?;
+
+        let active = TuiConfig::global().lock().unwrap().active_profile.clone();
+        let mut reports = Vec::new();
+        for ((uuid, name), result) in targets.into_iter().zip(results) {
+            let result = match result {
+                Ok(_) if active.as_ref() == Some(&uuid) => Self::reactivate()
+                    .await
+                    .with_context(|| "updated, but could not re-activate"),
+                result => result,
+            };
+            reports.push((name, result));
+        }
+
+        Ok(reports)
+    }
+
+    /// Whether any profile is waiting for a scheduled update
+    pub fn has_due() -> bool {
+        let now = unix_now();
+        Self::get_all()
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|p| p.is_due(now))
+    }
+
+    /// Subscriptions nearing expiry or quota, by the configured thresholds
+    pub fn get_warnings() -> Vec<String> {
+        let (warning_days, warning_percent) = {
+            let config = TuiConfig::global().lock().unwrap();
+            (config.expiry_warning_days, config.quota_warning_percent)
+        };
+        let now = unix_now();
+
+        Self::get_all()
+            .lock()
+            .unwrap()
+            .iter()
+            .flat_map(|p| p.get_warnings(now, warning_days, warning_percent))
+            .collect()
+    }
+
+    async fn fetch(uuid: &str) -> Result<()> {
+        // Download on a copy so the list stays unlocked meanwhile
+        let mut profile = {
+            let mut profiles = Self::get_all().lock().unwrap();
+            let profile = profiles
+                .iter_mut()
+                .find(|p| p.uuid == uuid)
+                .ok_or(anyhow!("profile `{}` not found", uuid))?;
+            profile.updating = true;
+            profile.clone()
+        };
+        let _guard = UpdatingGuard(uuid);
+
+        let result = profile.update().await;
+
+        let mut profiles = Self::get_all().lock().unwrap();
+        if let Some(p) = profiles.iter_mut().find(|p| p.uuid == uuid) {
+            if result.is_ok() {
+                p.updated_at = profile.updated_at;
+                p.expired_at = profile.expired_at;
+                p.traffics = profile.traffics;
+            }
+        }
+
+        result
     }
 
     pub async fn active_fallback_profile() -> Result<()> {
@@ -66,33 +178,63 @@ impl ProfileManager {
         let mut value = serde_yaml::from_str::<Value>("{}")?;
         Profile::apply_tui_config(&mut value).await?;
 
-        // Rewrite mihomo config
-        let path = PathBuf::from_str(
+        // Rewrite mihomo config and reload mihomo
+        let data_dir = PathBuf::from_str(
             &TuiConfig::global()
-                .mihomo_data_dir
+                .lock()
+                .unwrap()
+                .controller()
+                .data_dir
                 .clone()
                 .ok_or(anyhow!("mihomo data directory not set"))?,
-        )?
-        .join("config.yaml");
-        let mut file = File::create(&path)
-            .await
-            .with_context(|| format!("could not create file `{}`", path.display()))?;
-        file.write_all(serde_yaml::to_string(&value)?.as_bytes())
-            .await
-            .with_context(|| format!("could not write buffer for file `{}`", path.display()))?;
-        file.flush()
-            .await
-            .with_context(|| format!("could not flush buffer for file `{}`", path.display()))?;
+        )?;
+        apply_config(&data_dir, &serde_yaml::to_string(&value)?).await?;
 
-        // Reload mihomo
-        TuiConfig::global()
-            .get_mihomo_api()
-            .restart()
-            .await
-            .with_context(|| "could not restart mihomo core")?;
+        TuiConfig::set_active_profile(None).await?;
 
         Ok(())
     }
+
+    pub async fn reactivate() -> Result<()> {
+        let uuid = TuiConfig::global().lock().unwrap().active_profile.clone();
+        let profile = uuid.and_then(|uuid| {
+            Self::get_all()
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|p| p.uuid == uuid)
+                .cloned()
+        });
+
+        match profile {
+            Some(profile) => profile.activate().await,
+            None => Self::active_fallback_profile().await,
+        }
+    }
+
+    pub fn get_active_name() -> Option<String> {
+        match TuiConfig::global().lock().unwrap().active_profile.clone() {
+            Some(uuid) => Self::get_all()
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|p| p.uuid == uuid)
+                .map(|p| p.name.clone()),
+            None => Some("Fallback".into()),
+        }
+    }
+}
+
+/// Clears the updating flag even when the fetch is cancelled midway
+struct UpdatingGuard<'a>(&'a str);
+
+impl Drop for UpdatingGuard<'_> {
+    fn drop(&mut self) {
+        let mut profiles = ProfileManager::get_all().lock().unwrap();
+        if let Some(p) = profiles.iter_mut().find(|p| p.uuid == self.0) {
+            p.updating = false;
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -118,6 +260,11 @@ pub struct Profile {
 
     #[serde(skip)]
     pub updating: bool,
+
+    /// Last scheduled attempt, so failures are not retried on every check
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub scheduled_at: Option<u64>,
 }
 
 impl Default for Profile {
@@ -130,6 +277,7 @@ impl Default for Profile {
             expired_at: None,
             traffics: None,
             updating: false,
+            scheduled_at: None,
         }
     }
 }
@@ -177,7 +325,7 @@ impl Profile {
                         Err(_) => continue,
                     };
 
-                    match items.get(0).unwrap() {
+                    match items.first().unwrap() {
                         &"upload" | &"download" => {
                             if let Some(old) = used {
                                 used = Some(old + value)
@@ -186,7 +334,8 @@ impl Profile {
                             }
                         }
                         &"total" => total = Some(value),
-                        &"expire" => expired_at = Some(value),
+                        // Providers send 0 for subscriptions that never expire
+                        &"expire" if value > 0 => expired_at = Some(value),
                         _ => (),
                     }
                 }
@@ -195,8 +344,8 @@ impl Profile {
                 self.expired_at = expired_at;
             }
 
-            // Parse body
-            let body = r.text().await?;
+            // Parse body, plain proxy lists are turned into Clash YAML
+            let body = subscription::convert(&r.text().await?)?;
             let file_path = get_profiles_dir().join(format!("{}.yaml", self.uuid));
             let mut file = File::create(file_path).await?;
             file.write_all(body.as_bytes()).await?;
@@ -216,6 +365,7 @@ impl Profile {
         Ok(())
     }
 
+    #[cfg(test)]
     pub async fn update_script(&self, script: String) -> Result<()> {
         let file_path = get_profiles_dir().join(format!("{}.js", self.uuid));
         let mut file = File::create(file_path).await?;
@@ -230,6 +380,29 @@ impl Profile {
         Ok(fs::read_to_string(path).await?)
     }
 
+    pub async fn write_raw(&self, contents: &str) -> Result<()> {
+        let file_path = get_profiles_dir().join(format!("{}.yaml", self.uuid));
+        let mut file = File::create(file_path).await?;
+        file.write_all(contents.as_bytes()).await?;
+        file.flush().await?;
+
+        Ok(())
+    }
+
+    pub async fn remove_files(&self) -> Result<()> {
+        for ext in ["yaml", "js"] {
+            let path = get_profiles_dir().join(format!("{}.{}", self.uuid, ext));
+
+            if fs::try_exists(&path).await? {
+                fs::remove_file(&path)
+                    .await
+                    .with_context(|| format!("could not remove file `{}`", path.display()))?;
+            }
+        }
+
+        Ok(())
+    }
+
     pub async fn activate(&self) -> Result<()> {
         // Read profile
         let contents = self.read_raw().await?;
@@ -246,21 +419,55 @@ impl Profile {
         // Apply TUI config
         Self::apply_tui_config(&mut value).await?;
 
-        // Rewrite mihomo config
-        let path = PathBuf::from_str(
+        // Refuse configs that would leave the core down
+        let data_dir = PathBuf::from_str(
             &TuiConfig::global()
-                .mihomo_data_dir
+                .lock()
+                .unwrap()
+                .controller()
+                .data_dir
                 .clone()
                 .ok_or(anyhow!("mihomo data directory not set"))?,
-        )?
-        .join("config.yaml");
-        let mut file = File::create(path).await?;
-        file.write_all(serde_yaml::to_string(&value)?.as_bytes())
-            .await?;
-        file.flush().await?;
+        )?;
+        let contents = serde_yaml::to_string(&value)?;
+        Self::check(&value, &contents, &data_dir).await?;
+
+        // Rewrite mihomo config and reload mihomo
+        apply_config(&data_dir, &contents).await?;
+
+        TuiConfig::set_active_profile(Some(self.uuid.clone())).await?;
 
-        // Reload mihomo
-        TuiConfig::global().get_mihomo_api().restart().await?;
+        Ok(())
+    }
+
+    /// Logs every problem found, fails on errors or when the core rejects the config
+    async fn check(value: &Value, contents: &str, data_dir: &Path) -> Result<()> {
+        let problems = validation::validate(value);
+        let errors = problems
+            .iter()
+            .filter(|p| p.severity == Severity::Error)
+            .count();
+
+        {
+            let mut logger = Logger::get_instance().lock().unwrap();
+            for problem in problems {
+                match problem.severity {
+                    Severity::Warning => logger.warn(problem.message),
+                    Severity::Error => logger.error(problem.message),
+                }
+            }
+        }
+        if errors > 0 {
+            bail!(
+                "refusing to activate a profile with errors ({} found)",
+                errors
+            );
+        }
+
+        let binary = TuiConfig::global().lock().unwrap().mihomo_binary.clone();
+        if let Some(binary) = binary {
+            validation::test_with_core(&binary, data_dir, contents).await?;
+        }
 
         Ok(())
     }
@@ -301,7 +508,7 @@ impl Profile {
     }
 
     async fn apply_tui_config(value: &mut Value) -> Result<()> {
-        let config = TuiConfig::global();
+        let config = TuiConfig::global().lock().unwrap().clone();
 
         // Set mode
         value
@@ -312,22 +519,91 @@ impl Profile {
         Ok(())
     }
 
+    pub fn is_due(&self, now: u64) -> bool {
+        let Some(interval) = self
+            .remote
+            .as_ref()
+            .and_then(|r| r.update_interval)
+            .filter(|i| *i > 0)
+            .map(|i| i * 60)
+        else {
+            return false;
+        };
+
+        !self.updating
+            && now >= self.updated_at.unwrap_or_default() + interval
+            && self
+                .scheduled_at
+                .is_none_or(|t| now >= t + interval.min(SCHEDULE_RETRY_SECS))
+    }
+
     pub fn get_used_str(&self) -> Option<String> {
-        if let Some(traffics) = &self.traffics {
-            if traffics.total.is_none() || traffics.used.is_none() {
-                None
-            } else {
-                Some(format!(
-                    "{:.1}%",
-                    traffics.used.unwrap() as f64 / traffics.total.unwrap() as f64 * 100.0
-                ))
-            }
-        } else {
-            None
+        self.get_used_ratio()
+            .map(|ratio| format!("{:.1}%", ratio * 100.0))
+    }
+
+    fn get_used_ratio(&self) -> Option<f64> {
+        let traffics = self.traffics.as_ref()?;
+        let (used, total) = traffics.used.zip(traffics.total.filter(|t| *t > 0))?;
+
+        Some(used as f64 / total as f64)
+    }
+
+    /// Bytes left of the quota, when the provider reports one
+    pub fn get_remaining(&self) -> Option<u64> {
+        let traffics = self.traffics.as_ref()?;
+        let (used, total) = traffics.used.zip(traffics.total.filter(|t| *t > 0))?;
+
+        Some(total.saturating_sub(used))
+    }
+
+    /// Whole days until expiry, negative once expired
+    pub fn get_days_left(&self, now: u64) -> Option<i64> {
+        self.expired_at
+            .map(|t| (t as i64 - now as i64).div_euclid(24 * 60 * 60))
+    }
+
+    pub fn is_expiring(&self, now: u64, warning_days: u64) -> bool {
+        self.get_days_left(now)
+            .is_some_and(|days| days <= warning_days as i64)
+    }
+
+    pub fn is_over_quota(&self, warning_percent: u8) -> bool {
+        self.get_used_ratio()
+            .is_some_and(|ratio| ratio * 100.0 >= warning_percent as f64)
+    }
+
+    pub fn get_warnings(&self, now: u64, warning_days: u64, warning_percent: u8) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        if self.is_expiring(now, warning_days) {
+            warnings.push(match self.get_days_left(now).unwrap() {
+                days if days < 0 => format!("Profile \"{}\" has expired", self.name),
+                0 => format!("Profile \"{}\" expires today", self.name),
+                1 => format!("Profile \"{}\" expires in 1 day", self.name),
+                days => format!("Profile \"{}\" expires in {} days", self.name, days),
+            });
+        }
+        if self.is_over_quota(warning_percent) {
+            warnings.push(format!(
+                "Profile \"{}\" used {} of its quota, {} left",
+                self.name,
+                self.get_used_str().unwrap(),
+                format_bytes(self.get_remaining().unwrap())
+            ));
         }
+
+        warnings
     }
 }
 
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ProfileRemote {
     pub url: String,
@@ -344,6 +620,11 @@ pub struct ProfileRemote {
     #[serde(skip_serializing_if = "is_false")]
     #[serde(default)]
     pub allow_invalid_certificates: bool,
+
+    /// Minutes between scheduled updates, never when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub update_interval: Option<u64>,
 }
 
 impl Default for ProfileRemote {
@@ -354,6 +635,7 @@ impl Default for ProfileRemote {
             use_system_proxy: false,
             use_mihomo_proxy: false,
             allow_invalid_certificates: false,
+            update_interval: None,
         }
     }
 }
@@ -372,3 +654,417 @@ pub struct ProfileTraffics {
 fn is_false(b: &bool) -> bool {
     !b
 }
+
+/// Swaps in the new mihomo config and restarts the core, the previous config is
+/// kept as `config.yaml.bak` and restored when the core does not come back
+async fn apply_config(data_dir: &Path, contents: &str) -> Result<()> {
+    let path = data_dir.join("config.yaml");
+    let backup = data_dir.join("config.yaml.bak");
+
+    let has_backup = fs::try_exists(&path).await?;
+    if has_backup {
+        fs::copy(&path, &backup)
+            .await
+            .with_context(|| format!("could not back up file `{}`", path.display()))?;
+    }
+    write_atomic(&path, contents).await?;
+
+    let err = match restart_core().await {
+        Ok(_) => return Ok(()),
+        Err(err) => err,
+    };
+    if !has_backup {
+        return Err(err);
+    }
+
+    Logger::get_instance()
+        .lock()
+        .unwrap()
+        .warn(format!("Restoring previous mihomo config: {:#}", err));
+    let previous = fs::read_to_string(&backup)
+        .await
+        .with_context(|| format!("could not read file `{}`", backup.display()))?;
+    write_atomic(&path, &previous).await?;
+    if let Err(rollback_err) = restart_core().await {
+        return Err(err.context(format!(
+            "could not restore previous config: {:#}",
+            rollback_err
+        )));
+    }
+
+    Err(err.context("previous config restored"))
+}
+
+/// Restarts the core and waits until it answers again
+async fn restart_core() -> Result<()> {
+    let (api, timeout) = {
+        let config = TuiConfig::global().lock().unwrap();
+        (
+            config.get_mihomo_api(),
+            Duration::from_millis(config.core_restart_timeout),
+        )
+    };
+    api.restart()
+        .await
+        .with_context(|| "could not restart mihomo core")?;
+
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        tokio::time::sleep(RESTART_POLL_INTERVAL).await;
+        match api.get_version().await {
+            Ok(_) => return Ok(()),
+            Err(err) if tokio::time::Instant::now() >= deadline => {
+                return Err(anyhow!(err).context(format!(
+                    "mihomo core did not come back within {}ms",
+                    timeout.as_millis()
+                )))
+            }
+            Err(_) => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use tempfile::TempDir;
+    use tokio::sync::MutexGuard;
+
+    use super::*;
+    use crate::{
+        config::tui::{Controller, TuiConfigMode},
+        utils::{api::mock::MockController, testing::lock_globals},
+    };
+
+    /// Points the global config at the mock
+    async fn setup(mock: &MockController, mihomo_dir: &TempDir) -> MutexGuard<'static, ()> {
+        let guard = lock_globals().await;
+
+        let mut config = TuiConfig::global().lock().unwrap();
+        config.controllers = vec![Controller {
+            name: "mock".into(),
+            api: mock.url.clone(),
+            secret: None,
+            data_dir: Some(mihomo_dir.path().to_string_lossy().into()),
+            ..Default::default()
+        }];
+        config.mode = TuiConfigMode::Global;
+
+        guard
+    }
+
+    async fn create_profile(contents: &str) -> Profile {
+        let profile = Profile::default();
+        profile.write_raw(contents).await.unwrap();
+        profile
+    }
+
+    fn read_generated(mihomo_dir: &TempDir) -> Value {
+        let raw = std_fs::read_to_string(mihomo_dir.path().join("config.yaml")).unwrap();
+        serde_yaml::from_str(&raw).unwrap()
+    }
+
+    #[tokio::test]
+    async fn activate_writes_config_and_restarts() {
+        let mock = MockController::start().await;
+        let mihomo_dir = TempDir::new().unwrap();
+        let _guard = setup(&mock, &mihomo_dir).await;
+
+        let profile = create_profile("mixed-port: 7890\nmode: rule\n").await;
+        profile.activate().await.unwrap();
+
+        let generated = read_generated(&mihomo_dir);
+        assert_eq!(generated["mixed-port"], 7890);
+        assert_eq!(generated["mode"], "global");
+        assert_eq!(mock.state().restarts, 1);
+        assert_eq!(
+            TuiConfig::global().lock().unwrap().active_profile,
+            Some(profile.uuid.clone())
+        );
+    }
+
+    #[tokio::test]
+    async fn activate_applies_extend_script() {
+        let mock = MockController::start().await;
+        let mihomo_dir = TempDir::new().unwrap();
+        let _guard = setup(&mock, &mihomo_dir).await;
+
+        let profile = create_profile("allow-lan: false\n").await;
+        profile
+            .update_script(
+                // The config is handed over as a JSON string
+                "function main(raw) { const config = JSON.parse(raw); config['allow-lan'] = true; return config; }".into(),
+            )
+            .await
+            .unwrap();
+        profile.activate().await.unwrap();
+
+        assert_eq!(read_generated(&mihomo_dir)["allow-lan"], true);
+    }
+
+    #[tokio::test]
+    async fn activate_keeps_previous_config_as_backup() {
+        let mock = MockController::start().await;
+        let mihomo_dir = TempDir::new().unwrap();
+        let _guard = setup(&mock, &mihomo_dir).await;
+
+        create_profile("mixed-port: 7890\n")
+            .await
+            .activate()
+            .await
+            .unwrap();
+        create_profile("mixed-port: 7891\n")
+            .await
+            .activate()
+            .await
+            .unwrap();
+
+        assert_eq!(read_generated(&mihomo_dir)["mixed-port"], 7891);
+        let raw = std_fs::read_to_string(mihomo_dir.path().join("config.yaml.bak")).unwrap();
+        let backup = serde_yaml::from_str::<Value>(&raw).unwrap();
+        assert_eq!(backup["mixed-port"], 7890);
+        let leftovers = std_fs::read_dir(mihomo_dir.path())
+            .unwrap()
+            .filter(|entry| {
+                entry
+                    .as_ref()
+                    .unwrap()
+                    .file_name()
+                    .to_string_lossy()
+                    .ends_with(".tmp")
+            })
+            .count();
+        assert_eq!(leftovers, 0);
+    }
+
+    #[tokio::test]
+    async fn activate_rolls_back_when_core_does_not_come_back() {
+        let mock = MockController::start().await;
+        let mihomo_dir = TempDir::new().unwrap();
+        let _guard = setup(&mock, &mihomo_dir).await;
+        TuiConfig::global().lock().unwrap().core_restart_timeout = 100;
+
+        let working = create_profile("mixed-port: 7890\n").await;
+        working.activate().await.unwrap();
+
+        // The core stays silent for the whole timeout after the second restart
+        mock.fail_next(
+            Method::GET,
+            "/version",
+            StatusCode::SERVICE_UNAVAILABLE,
+            "core is down",
+        );
+        let err = create_profile("mixed-port: 7891\n")
+            .await
+            .activate()
+            .await
+            .unwrap_err();
+
+        let message = format!("{:#}", err);
+        assert!(message.contains("previous config restored"));
+        assert!(message.contains("did not come back within 100ms"));
+        assert_eq!(read_generated(&mihomo_dir)["mixed-port"], 7890);
+        assert_eq!(mock.state().restarts, 3);
+        assert_eq!(
+            TuiConfig::global().lock().unwrap().active_profile,
+            Some(working.uuid)
+        );
+    }
+
+    #[tokio::test]
+    async fn activate_keeps_active_profile_on_restart_failure() {
+        let mock = MockController::start().await;
+        let mihomo_dir = TempDir::new().unwrap();
+        let _guard = setup(&mock, &mihomo_dir).await;
+
+        mock.fail_next(
+            Method::POST,
+            "/restart",
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "restart failed",
+        );
+        let profile = create_profile("mode: rule\n").await;
+        let err = profile.activate().await.unwrap_err();
+
+        assert!(format!("{:#}", err).contains("restart failed"));
+        assert_eq!(TuiConfig::global().lock().unwrap().active_profile, None);
+    }
+
+    #[tokio::test]
+    async fn activate_rejects_invalid_profile() {
+        let mock = MockController::start().await;
+        let mihomo_dir = TempDir::new().unwrap();
+        let _guard = setup(&mock, &mihomo_dir).await;
+
+        let profile = create_profile("- not\n- a mapping\n").await;
+        assert!(profile.activate().await.is_err());
+        assert_eq!(mock.state().restarts, 0);
+    }
+
+    #[tokio::test]
+    async fn activate_refuses_broken_references() {
+        let mock = MockController::start().await;
+        let mihomo_dir = TempDir::new().unwrap();
+        let _guard = setup(&mock, &mihomo_dir).await;
+
+        let profile = create_profile(
+            "proxy-groups:\n  - { name: PROXY, type: select, proxies: [gone] }\nrules:\n  - MATCH,nowhere\n",
+        )
+        .await;
+        let err = profile.activate().await.unwrap_err();
+
+        assert!(format!("{:#}", err).contains("2 found"));
+        assert!(!mihomo_dir.path().join("config.yaml").exists());
+        assert_eq!(mock.state().restarts, 0);
+
+        let logger = Logger::get_instance().lock().unwrap();
+        let log = logger.get_buffer();
+        assert_eq!(log.len(), 2);
+        assert!(log[0]
+            .1
+            .ends_with("proxy group `PROXY` references unknown proxy `gone`"));
+        assert!(log[1]
+            .1
+            .ends_with("rule `MATCH,nowhere` targets unknown `nowhere`"));
+    }
+
+    // The stub core is a shell script
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn activate_tests_config_with_core() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mock = MockController::start().await;
+        let mihomo_dir = TempDir::new().unwrap();
+        let _guard = setup(&mock, &mihomo_dir).await;
+
+        // Stands in for the core, fails when the config mentions `broken`
+        let binary = mihomo_dir.path().join("mihomo");
+        std_fs::write(
+            &binary,
+            "#!/bin/sh\nif grep -q broken \"$5\"; then\n  echo 'level=error msg=\"broken config\"'\n  exit 1\nfi\n",
+        )
+        .unwrap();
+        std_fs::set_permissions(&binary, std_fs::Permissions::from_mode(0o755)).unwrap();
+        TuiConfig::global().lock().unwrap().mihomo_binary = Some(binary.to_string_lossy().into());
+
+        let profile = create_profile("log-level: broken\n").await;
+        let err = profile.activate().await.unwrap_err();
+        assert!(format!("{:#}", err).contains("level=error msg=\"broken config\""));
+        assert_eq!(mock.state().restarts, 0);
+
+        let profile = create_profile("log-level: info\n").await;
+        profile.activate().await.unwrap();
+        assert_eq!(mock.state().restarts, 1);
+
+        // The scratch file is cleaned up
+        assert!(!mihomo_dir.path().join(".config.check.yaml").exists());
+    }
+
+    #[tokio::test]
+    async fn reactivate_falls_back_without_active_profile() {
+        let mock = MockController::start().await;
+        let mihomo_dir = TempDir::new().unwrap();
+        let _guard = setup(&mock, &mihomo_dir).await;
+
+        ProfileManager::reactivate().await.unwrap();
+
+        assert_eq!(read_generated(&mihomo_dir)["mode"], "global");
+        assert_eq!(mock.state().restarts, 1);
+        assert_eq!(ProfileManager::get_active_name(), Some("Fallback".into()));
+    }
+
+    #[tokio::test]
+    async fn reports_unreachable_core() {
+        let mock = MockController::start().await;
+        let mihomo_dir = TempDir::new().unwrap();
+        let _guard = setup(&mock, &mihomo_dir).await;
+        TuiConfig::global().lock().unwrap().controllers[0].api = "http://127.0.0.1:1".into();
+
+        let err = ProfileManager::active_fallback_profile().await.unwrap_err();
+        assert!(format!("{:#}", err).contains("could not restart mihomo core"));
+    }
+
+    #[tokio::test]
+    async fn update_due_persists_failed_attempts() {
+        let _guard = lock_globals().await;
+        *ProfileManager::get_all().lock().unwrap() = vec![Profile {
+            remote: Some(ProfileRemote {
+                url: "http://127.0.0.1:1/sub".into(),
+                update_interval: Some(60),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }];
+
+        let reports = ProfileManager::update_due().await.unwrap();
+        assert!(reports[0].1.is_err());
+
+        // The next headless run keeps waiting for the retry delay
+        let raw = std_fs::read_to_string(get_data_dir().join("profiles.yaml")).unwrap();
+        let saved = serde_yaml::from_str::<Vec<Profile>>(&raw).unwrap();
+        assert!(saved[0].scheduled_at.is_some());
+        *ProfileManager::get_all().lock().unwrap() = saved;
+        assert!(ProfileManager::update_due().await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn schedules_by_update_interval() {
+        let mut profile = Profile {
+            remote: Some(ProfileRemote {
+                update_interval: Some(60),
+                ..Default::default()
+            }),
+            updated_at: Some(10_000),
+            ..Default::default()
+        };
+
+        assert!(!profile.is_due(10_000 + 59 * 60));
+        assert!(profile.is_due(10_000 + 60 * 60));
+
+        // A failed attempt waits for the retry delay
+        profile.scheduled_at = Some(20_000);
+        assert!(!profile.is_due(20_000 + 9 * 60));
+        assert!(profile.is_due(20_000 + 10 * 60));
+
+        profile.updating = true;
+        assert!(!profile.is_due(30_000));
+
+        profile.updating = false;
+        profile.remote.as_mut().unwrap().update_interval = None;
+        assert!(!profile.is_due(30_000));
+    }
+
+    #[test]
+    fn warns_about_expiry_and_quota() {
+        let day = 24 * 60 * 60;
+        let mut profile = Profile {
+            name: "Sub".into(),
+            expired_at: Some(10 * day),
+            traffics: Some(ProfileTraffics {
+                used: Some(950 * 1024 * 1024),
+                total: Some(1024 * 1024 * 1024),
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(profile.get_days_left(6 * day + 1), Some(3));
+        assert!(profile.get_warnings(6 * day + 1, 2, 100).is_empty());
+        assert_eq!(
+            profile.get_warnings(6 * day + 1, 3, 90),
+            [
+                "Profile \"Sub\" expires in 3 days",
+                "Profile \"Sub\" used 92.8% of its quota, 74.00 MiB left",
+            ]
+        );
+        assert_eq!(
+            profile.get_warnings(10 * day + 1, 3, 100),
+            ["Profile \"Sub\" has expired"]
+        );
+
+        // Unlimited quotas are reported with a zero total
+        profile.traffics.as_mut().unwrap().total = Some(0);
+        assert_eq!(profile.get_remaining(), None);
+        assert!(!profile.is_over_quota(0));
+    }
+}
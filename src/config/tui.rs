@@ -1,61 +1,353 @@
-use std::{fs, sync::OnceLock};
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
+use reqwest::Url;
 use serde::{Deserialize, Serialize};
-use tokio::{fs::File, io::AsyncWriteExt};
 
-use crate::utils::{api::MihomoApi, path::get_data_dir};
+use crate::utils::{
+    api::{tls::TlsOptions, MihomoApi, MihomoApiOptions},
+    path::get_data_dir,
+};
+
+use super::write_atomic;
+
+/// A named mihomo instance, e.g. one on a laptop and one on a router
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Controller {
+    pub name: String,
+
+    pub api: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub secret: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub data_dir: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub ca_cert: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub client_cert: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub client_key: Option<String>,
+
+    /// SHA-256 of the controller certificate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+}
+
+impl Default for Controller {
+    fn default() -> Self {
+        Self {
+            name: "default".into(),
+            api: "http://localhost:9090".into(),
+            secret: None,
+            data_dir: None,
+            ca_cert: None,
+            client_cert: None,
+            client_key: None,
+            fingerprint: None,
+        }
+    }
+}
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct TuiConfig {
-    pub controller_api: String,
+    #[serde(default)]
+    pub controllers: Vec<Controller>,
+
+    /// Name of the controller in use
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub active_controller: Option<String>,
+
+    // Single controller settings of older versions, moved into `controllers`
+    #[serde(rename = "controller_api", skip_serializing, default)]
+    legacy_api: Option<String>,
+
+    #[serde(rename = "controller_api_secret", skip_serializing, default)]
+    legacy_secret: Option<String>,
+
+    #[serde(rename = "mihomo_data_dir", skip_serializing, default)]
+    legacy_data_dir: Option<String>,
+
+    pub mode: TuiConfigMode,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
-    pub controller_api_secret: Option<String>,
+    pub active_profile: Option<String>,
+
+    /// In milliseconds
+    #[serde(default = "default_connect_timeout")]
+    pub controller_connect_timeout: u64,
+
+    /// In milliseconds
+    #[serde(default = "default_request_timeout")]
+    pub controller_request_timeout: u64,
+
+    #[serde(default = "default_retries")]
+    pub controller_retries: u32,
 
+    /// In milliseconds, how long the core gets to answer again after a restart
+    #[serde(default = "default_restart_timeout")]
+    pub core_restart_timeout: u64,
+
+    /// Core binary used to test configs with `mihomo -t` before activation
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
-    pub mihomo_data_dir: Option<String>,
+    pub mihomo_binary: Option<String>,
 
-    pub mode: TuiConfigMode,
+    /// Warn when a subscription expires within this many days
+    #[serde(default = "default_expiry_warning_days")]
+    pub expiry_warning_days: u64,
+
+    /// Warn when a subscription used this percentage of its quota
+    #[serde(default = "default_quota_warning_percent")]
+    pub quota_warning_percent: u8,
+}
+
+impl Default for TuiConfig {
+    fn default() -> Self {
+        Self {
+            controllers: vec![Controller::default()],
+            active_controller: None,
+            legacy_api: None,
+            legacy_secret: None,
+            legacy_data_dir: None,
+            mode: TuiConfigMode::Direct,
+            active_profile: None,
+            controller_connect_timeout: default_connect_timeout(),
+            controller_request_timeout: default_request_timeout(),
+            controller_retries: default_retries(),
+            core_restart_timeout: default_restart_timeout(),
+            mihomo_binary: None,
+            expiry_warning_days: default_expiry_warning_days(),
+            quota_warning_percent: default_quota_warning_percent(),
+        }
+    }
 }
 
 impl TuiConfig {
-    pub fn global() -> &'static Self {
-        static INSTANCE: OnceLock<TuiConfig> = OnceLock::new();
+    pub fn global() -> &'static Mutex<Self> {
+        static INSTANCE: OnceLock<Mutex<TuiConfig>> = OnceLock::new();
         INSTANCE.get_or_init(|| {
             let path = get_data_dir().join("config.yaml");
 
             if fs::exists(&path).unwrap() {
-                serde_yaml::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+                let mut config: Self =
+                    serde_yaml::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+                config.migrate();
+                Mutex::new(config)
             } else {
-                Self {
-                    controller_api: "http://localhost:9090".into(),
-                    controller_api_secret: None,
-                    mihomo_data_dir: None,
-                    mode: TuiConfigMode::Direct,
-                }
+                Mutex::new(Self::default())
             }
         })
     }
 
     pub async fn flush(&self) -> Result<()> {
         let file_path = get_data_dir().join("config.yaml");
-        let mut file = File::create(file_path).await?;
-        file.write_all(serde_yaml::to_string(&self)?.as_bytes())
-            .await?;
-        file.flush().await?;
+        write_atomic(&file_path, &serde_yaml::to_string(&self)?).await
+    }
+
+    /// Moves the legacy single controller into the named list
+    fn migrate(&mut self) {
+        if let Some(api) = self.legacy_api.take() {
+            if self.controllers.is_empty() {
+                self.controllers.push(Controller {
+                    name: Controller::default().name,
+                    api,
+                    secret: self.legacy_secret.take(),
+                    data_dir: self.legacy_data_dir.take(),
+                    ..Default::default()
+                });
+            }
+        }
+        self.legacy_secret = None;
+        self.legacy_data_dir = None;
+
+        if self.controllers.is_empty() {
+            self.controllers.push(Controller::default());
+        }
+    }
+
+    /// The active controller, falls back to the first one if the name is stale
+    pub fn controller(&self) -> &Controller {
+        &self.controllers[self.controller_index()]
+    }
+
+    pub fn controller_index(&self) -> usize {
+        self.active_controller
+            .as_ref()
+            .and_then(|name| self.controllers.iter().position(|c| c.name == *name))
+            .unwrap_or(0)
+    }
+
+    pub async fn set_active_controller(name: &str) -> Result<()> {
+        let config = {
+            let mut config = Self::global().lock().unwrap();
+            if !config.controllers.iter().any(|c| c.name == name) {
+                bail!("controller `{}` does not exist", name);
+            }
+            config.active_controller = Some(name.to_owned());
+            config.clone()
+        };
+
+        config.flush().await
+    }
+
+    pub async fn set_active_profile(uuid: Option<String>) -> Result<()> {
+        let config = {
+            let mut config = Self::global().lock().unwrap();
+            config.active_profile = uuid;
+            config.clone()
+        };
+
+        config.flush().await
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.controllers.is_empty() {
+            bail!("at least one controller is required");
+        }
+
+        if self.controller_connect_timeout == 0 || self.controller_request_timeout == 0 {
+            bail!("controller timeouts must be greater than 0");
+        }
+
+        if self.core_restart_timeout == 0 {
+            bail!("restart timeout must be greater than 0");
+        }
+
+        if self.quota_warning_percent > 100 {
+            bail!("quota warning must be a percentage up to 100");
+        }
+
+        for (i, controller) in self.controllers.iter().enumerate() {
+            if controller.name.is_empty() {
+                bail!("controller name must not be empty");
+            }
+            if self.controllers[..i]
+                .iter()
+                .any(|c| c.name == controller.name)
+            {
+                bail!("controller name `{}` is used twice", controller.name);
+            }
+
+            controller.validate()?;
+        }
 
         Ok(())
     }
 
+    fn get_api_options(&self) -> MihomoApiOptions {
+        MihomoApiOptions {
+            connect_timeout: Duration::from_millis(self.controller_connect_timeout),
+            request_timeout: Duration::from_millis(self.controller_request_timeout),
+            retries: self.controller_retries,
+            tls: self.controller().get_tls_options(),
+        }
+    }
+
+    /// Clones share one pooled client until the controller settings change
     pub fn get_mihomo_api(&self) -> MihomoApi {
-        MihomoApi::new(&self.controller_api, self.controller_api_secret.as_ref())
+        type CacheKey = (String, Option<String>, MihomoApiOptions);
+        static CACHE: Mutex<Option<(CacheKey, MihomoApi)>> = Mutex::new(None);
+
+        let controller = self.controller();
+        let key = (
+            controller.api.clone(),
+            controller.secret.clone(),
+            self.get_api_options(),
+        );
+
+        let mut cache = CACHE.lock().unwrap();
+        match cache.as_ref() {
+            Some((cached, api)) if *cached == key => api.clone(),
+            _ => {
+                let api = MihomoApi::new(key.0.clone(), key.1.clone(), key.2.clone());
+                *cache = Some((key, api.clone()));
+                api
+            }
+        }
     }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+impl Controller {
+    pub fn get_tls_options(&self) -> TlsOptions {
+        TlsOptions {
+            ca_cert: self.ca_cert.as_ref().map(PathBuf::from),
+            client_cert: self.client_cert.as_ref().map(PathBuf::from),
+            client_key: self.client_key.as_ref().map(PathBuf::from),
+            fingerprint: self.fingerprint.clone(),
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        let url = match Url::parse(&self.api) {
+            Ok(url) => url,
+            Err(err) => bail!("invalid controller API `{}`: {}", self.api, err),
+        };
+        match url.scheme() {
+            "http" | "https" if url.host().is_some() => (),
+            "unix" if !url.path().is_empty() => (),
+            _ => bail!(
+                "controller API `{}` must be an http(s) URL with a host or a unix:// socket path",
+                self.api
+            ),
+        }
+
+        if let Some(dir) = &self.data_dir {
+            if !fs::metadata(dir).map(|m| m.is_dir()).unwrap_or(false) {
+                bail!("mihomo data directory `{}` does not exist", dir);
+            }
+        }
+
+        if url.scheme() == "https" {
+            self.get_tls_options().build_client_config()?;
+        }
+
+        Ok(())
+    }
+}
+
+fn default_connect_timeout() -> u64 {
+    MihomoApiOptions::default().connect_timeout.as_millis() as u64
+}
+
+fn default_request_timeout() -> u64 {
+    MihomoApiOptions::default().request_timeout.as_millis() as u64
+}
+
+fn default_retries() -> u32 {
+    MihomoApiOptions::default().retries
+}
+
+fn default_restart_timeout() -> u64 {
+    10_000
+}
+
+fn default_expiry_warning_days() -> u64 {
+    3
+}
+
+fn default_quota_warning_percent() -> u8 {
+    90
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum TuiConfigMode {
     Direct,
@@ -71,4 +363,41 @@ impl TuiConfigMode {
             Self::Rule => "rule",
         }
     }
+
+    pub const fn next(&self) -> Self {
+        match self {
+            Self::Direct => Self::Global,
+            Self::Global => Self::Rule,
+            Self::Rule => Self::Direct,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uses_tls_settings_of_the_active_controller() {
+        let mut config = serde_yaml::from_str::<TuiConfig>(
+            "controllers:
+- name: laptop
+  api: http://localhost:9090
+- name: router
+  api: https://192.168.1.1:9090
+  ca_cert: /etc/router.pem
+  fingerprint: ab:cd
+active_controller: router
+mode: rule
+",
+        )
+        .unwrap();
+
+        let tls = config.get_api_options().tls;
+        assert_eq!(tls.ca_cert, Some(PathBuf::from("/etc/router.pem")));
+        assert_eq!(tls.fingerprint.as_deref(), Some("ab:cd"));
+
+        config.active_controller = Some("laptop".into());
+        assert!(config.get_api_options().tls.is_default());
+    }
 }
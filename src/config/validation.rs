@@ -0,0 +1,427 @@
+use std::{collections::HashSet, path::Path, process::Stdio};
+
+use anyhow::{bail, Context, Result};
+use serde_yaml::{Mapping, Value};
+use tokio::{fs, process::Command};
+
+pub const BUILTIN_TARGETS: [&str; 6] = [
+    "DIRECT",
+    "REJECT",
+    "REJECT-DROP",
+    "PASS",
+    "COMPATIBLE",
+    "GLOBAL",
+];
+const GROUP_TYPES: [&str; 5] = ["select", "url-test", "fallback", "load-balance", "relay"];
+const LOGIC_RULES: [&str; 4] = ["AND", "OR", "NOT", "SUB-RULE"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Problem {
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Checks what mihomo would refuse to start with, returns every problem found
+pub fn validate(config: &Value) -> Vec<Problem> {
+    let mut checker = Checker::default();
+
+    let Some(config) = config.as_mapping() else {
+        checker.error("config is not a mapping");
+        return checker.problems;
+    };
+
+    let proxies = checker.sequence(config, "proxies");
+    let groups = checker.sequence(config, "proxy-groups");
+    let providers = checker.names_of(config, "proxy-providers");
+    let rule_providers = checker.names_of(config, "rule-providers");
+    let sub_rules = checker.names_of(config, "sub-rules");
+
+    // Proxies and groups share one namespace
+    let mut names = HashSet::new();
+    for (i, proxy) in proxies.iter().enumerate() {
+        if let Some(name) = checker.name(proxy, &format!("proxy #{}", i + 1)) {
+            checker.unique(&mut names, name);
+            checker.proxy(name, proxy);
+        }
+    }
+    let group_names = groups
+        .iter()
+        .enumerate()
+        .filter_map(|(i, group)| checker.name(group, &format!("proxy group #{}", i + 1)))
+        .collect::<Vec<&str>>();
+    for name in &group_names {
+        checker.unique(&mut names, name);
+    }
+
+    for group in &groups {
+        if let Some(name) = group["name"].as_str() {
+            checker.group(name, group, &names, &providers);
+        }
+    }
+
+    for (i, rule) in checker.sequence(config, "rules").iter().enumerate() {
+        match rule.as_str() {
+            Some(rule) => checker.rule(rule, &names, &rule_providers, &sub_rules),
+            None => checker.error(format!("rule #{} is not a string", i + 1)),
+        }
+    }
+
+    checker.problems
+}
+
+/// Runs `mihomo -t` on the config, relative paths resolve against the data directory
+pub async fn test_with_core(binary: &str, data_dir: &Path, contents: &str) -> Result<()> {
+    let path = data_dir.join(".config.check.yaml");
+    fs::write(&path, contents)
+        .await
+        .with_context(|| format!("could not write file `{}`", path.display()))?;
+
+    let output = Command::new(binary)
+        .arg("-t")
+        .arg("-d")
+        .arg(data_dir)
+        .arg("-f")
+        .arg(&path)
+        .stdin(Stdio::null())
+        .output()
+        .await;
+    let _ = fs::remove_file(&path).await;
+    let output = output.with_context(|| format!("could not run mihomo core `{}`", binary))?;
+
+    if !output.status.success() {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stdout
+            .lines()
+            .chain(stderr.lines())
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .find(|l| l.contains("level=error") || l.contains("test failed"))
+            .or(stderr
+                .lines()
+                .chain(stdout.lines())
+                .rfind(|l| !l.trim().is_empty()))
+            .unwrap_or("no output")
+            .to_owned();
+        bail!("mihomo core rejected the config: {}", reason);
+    }
+
+    Ok(())
+}
+
+#[derive(Default)]
+struct Checker {
+    problems: Vec<Problem>,
+}
+
+impl Checker {
+    fn error<S: Into<String>>(&mut self, message: S) {
+        self.problems.push(Problem {
+            severity: Severity::Error,
+            message: message.into(),
+        });
+    }
+
+    fn warn<S: Into<String>>(&mut self, message: S) {
+        self.problems.push(Problem {
+            severity: Severity::Warning,
+            message: message.into(),
+        });
+    }
+
+    fn sequence<'a>(&mut self, config: &'a Mapping, key: &str) -> Vec<&'a Value> {
+        match config.get(key) {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Sequence(items)) => items.iter().collect(),
+            Some(_) => {
+                self.error(format!("`{}` is not a list", key));
+                Vec::new()
+            }
+        }
+    }
+
+    fn names_of(&mut self, config: &Mapping, key: &str) -> HashSet<String> {
+        match config.get(key) {
+            None | Some(Value::Null) => HashSet::new(),
+            Some(Value::Mapping(items)) => items
+                .keys()
+                .filter_map(|k| k.as_str().map(str::to_owned))
+                .collect(),
+            Some(_) => {
+                self.error(format!("`{}` is not a mapping", key));
+                HashSet::new()
+            }
+        }
+    }
+
+    fn name<'a>(&mut self, item: &'a Value, what: &str) -> Option<&'a str> {
+        match item["name"].as_str() {
+            Some(name) if !name.is_empty() => Some(name),
+            _ => {
+                self.error(format!("{} has no name", what));
+                None
+            }
+        }
+    }
+
+    fn unique<'a>(&mut self, names: &mut HashSet<&'a str>, name: &'a str) {
+        // A user defined `GLOBAL` group customizes the builtin global selector
+        if BUILTIN_TARGETS.contains(&name) && name != "GLOBAL" {
+            self.error(format!("`{}` is a builtin name", name));
+        } else if !names.insert(name) {
+            self.error(format!("`{}` is defined twice", name));
+        }
+    }
+
+    fn proxy(&mut self, name: &str, proxy: &Value) {
+        let Some(proxy_type) = proxy["type"].as_str() else {
+            self.error(format!("proxy `{}` has no type", name));
+            return;
+        };
+
+        let required: &[&str] = match proxy_type {
+            "direct" | "reject" | "dns" => &[],
+            "http" | "socks5" | "hysteria" | "tuic" | "mieru" => &["server", "port"],
+            "ss" => &["server", "port", "cipher", "password"],
+            "ssr" => &["server", "port", "cipher", "password", "obfs", "protocol"],
+            "vmess" | "vless" => &["server", "port", "uuid"],
+            "trojan" | "hysteria2" | "anytls" => &["server", "port", "password"],
+            "snell" => &["server", "port", "psk"],
+            // Servers may be listed under `peers` instead
+            "wireguard" => &["private-key"],
+            "ssh" => &["server", "port", "username"],
+            _ => {
+                self.warn(format!(
+                    "proxy `{}` has unknown type `{}`",
+                    name, proxy_type
+                ));
+                return;
+            }
+        };
+        for field in required {
+            if proxy[field].is_null() {
+                self.error(format!(
+                    "{} proxy `{}` is missing `{}`",
+                    proxy_type, name, field
+                ));
+            }
+        }
+
+        if required.contains(&"port") && !proxy["port"].is_null() {
+            let port = match &proxy["port"] {
+                Value::Number(n) => n.as_u64(),
+                Value::String(s) => s.parse().ok(),
+                _ => None,
+            };
+            if !matches!(port, Some(1..=65535)) {
+                self.error(format!("proxy `{}` has an invalid port", name));
+            }
+        }
+    }
+
+    fn group(
+        &mut self,
+        name: &str,
+        group: &Value,
+        names: &HashSet<&str>,
+        providers: &HashSet<String>,
+    ) {
+        match group["type"].as_str() {
+            Some(t) if GROUP_TYPES.contains(&t) => (),
+            Some(t) => self.warn(format!("proxy group `{}` has unknown type `{}`", name, t)),
+            None => self.error(format!("proxy group `{}` has no type", name)),
+        }
+
+        let members = group["proxies"].as_sequence().cloned().unwrap_or_default();
+        for member in &members {
+            match member.as_str() {
+                Some(member) if member == name => {
+                    self.error(format!("proxy group `{}` contains itself", name))
+                }
+                Some(member) if names.contains(member) || BUILTIN_TARGETS.contains(&member) => (),
+                Some(member) => self.error(format!(
+                    "proxy group `{}` references unknown proxy `{}`",
+                    name, member
+                )),
+                None => self.error(format!("proxy group `{}` has a non-string member", name)),
+            }
+        }
+
+        let uses = group["use"].as_sequence().cloned().unwrap_or_default();
+        for provider in uses.iter().filter_map(Value::as_str) {
+            if !providers.contains(provider) {
+                self.error(format!(
+                    "proxy group `{}` uses unknown provider `{}`",
+                    name, provider
+                ));
+            }
+        }
+
+        let include_all = [
+            "include-all",
+            "include-all-proxies",
+            "include-all-providers",
+        ]
+        .iter()
+        .any(|k| group[k].as_bool() == Some(true));
+        if members.is_empty() && uses.is_empty() && !include_all {
+            self.error(format!("proxy group `{}` is empty", name));
+        }
+    }
+
+    fn rule(
+        &mut self,
+        rule: &str,
+        names: &HashSet<&str>,
+        rule_providers: &HashSet<String>,
+        sub_rules: &HashSet<String>,
+    ) {
+        let parts = rule.split(',').map(str::trim).collect::<Vec<&str>>();
+        let rule_type = parts[0];
+
+        let target = if LOGIC_RULES.contains(&rule_type) {
+            // The payload is parenthesized and may contain commas
+            rule.rfind(')').and_then(|i| {
+                rule[i + 1..]
+                    .split(',')
+                    .map(str::trim)
+                    .find(|s| !s.is_empty())
+            })
+        } else if rule_type == "MATCH" {
+            parts.get(1).copied()
+        } else {
+            parts.get(2).copied()
+        };
+        let Some(target) = target.filter(|t| !t.is_empty()) else {
+            self.error(format!("rule `{}` has no target", rule));
+            return;
+        };
+
+        if rule_type == "RULE-SET" && !rule_providers.contains(parts[1]) {
+            self.error(format!(
+                "rule `{}` references unknown rule provider `{}`",
+                rule, parts[1]
+            ));
+        }
+
+        let known = if rule_type == "SUB-RULE" {
+            sub_rules.contains(target)
+        } else {
+            names.contains(target) || BUILTIN_TARGETS.contains(&target)
+        };
+        if !known {
+            self.error(format!("rule `{}` targets unknown `{}`", rule, target));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(yaml: &str) -> Vec<String> {
+        validate(&serde_yaml::from_str(yaml).unwrap())
+            .into_iter()
+            .filter(|p| p.severity == Severity::Error)
+            .map(|p| p.message)
+            .collect()
+    }
+
+    #[test]
+    fn accepts_valid_config() {
+        let yaml = r#"
+proxies:
+  - { name: a, type: ss, server: 1.2.3.4, port: 8388, cipher: aes-256-gcm, password: x }
+  - { name: b, type: trojan, server: example.com, port: "443", password: x }
+proxy-providers:
+  sub: { type: http, url: "https://example.com" }
+proxy-groups:
+  - { name: PROXY, type: select, proxies: [auto, a, DIRECT], use: [sub] }
+  - { name: auto, type: url-test, proxies: [a, b] }
+  - { name: all, type: select, include-all: true }
+  - { name: GLOBAL, type: select, proxies: [PROXY, auto, DIRECT] }
+rule-providers:
+  ads: { type: http, behavior: domain, url: "https://example.com" }
+sub-rules:
+  lan: [MATCH,DIRECT]
+rules:
+  - RULE-SET,ads,REJECT
+  - IP-CIDR,10.0.0.0/8,DIRECT,no-resolve
+  - AND,((DOMAIN,example.com),(NETWORK,UDP)),auto
+  - SUB-RULE,(NETWORK,tcp),lan
+  - MATCH,PROXY
+"#;
+        assert!(validate(&serde_yaml::from_str(yaml).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn reports_broken_proxies() {
+        let yaml = r#"
+proxies:
+  - { name: a, type: vmess, server: example.com, port: 70000 }
+  - { name: a, type: ss, server: example.com, port: 1, cipher: none, password: x }
+  - { name: DIRECT, type: http, server: example.com, port: 80 }
+  - { type: http }
+  - { name: b, type: carrier-pigeon }
+"#;
+        assert_eq!(
+            errors(yaml),
+            [
+                "vmess proxy `a` is missing `uuid`",
+                "proxy `a` has an invalid port",
+                "`a` is defined twice",
+                "`DIRECT` is a builtin name",
+                "proxy #4 has no name",
+            ]
+        );
+        assert!(
+            validate(&serde_yaml::from_str(yaml).unwrap()).contains(&Problem {
+                severity: Severity::Warning,
+                message: "proxy `b` has unknown type `carrier-pigeon`".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn reports_broken_groups_and_rules() {
+        let yaml = r#"
+proxies:
+  - { name: a, type: http, server: example.com, port: 80 }
+proxy-groups:
+  - { name: PROXY, type: select, proxies: [a, missing, PROXY], use: [nowhere] }
+  - { name: empty, type: select }
+rules:
+  - RULE-SET,ads,REJECT
+  - DOMAIN,example.com,gone
+  - DOMAIN,example.com
+  - MATCH,PROXY
+"#;
+        assert_eq!(
+            errors(yaml),
+            [
+                "proxy group `PROXY` references unknown proxy `missing`",
+                "proxy group `PROXY` contains itself",
+                "proxy group `PROXY` uses unknown provider `nowhere`",
+                "proxy group `empty` is empty",
+                "rule `RULE-SET,ads,REJECT` references unknown rule provider `ads`",
+                "rule `DOMAIN,example.com,gone` targets unknown `gone`",
+                "rule `DOMAIN,example.com` has no target",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_wrong_shapes() {
+        assert_eq!(errors("[]"), ["config is not a mapping"]);
+        assert_eq!(
+            errors("proxies: {}\nrules: [1]"),
+            ["`proxies` is not a list", "rule #1 is not a string"]
+        );
+    }
+}
@@ -9,9 +9,12 @@ use tokio::{
     time,
 };
 
+use crate::task::{TaskEvent, TaskManager};
+
 pub enum Event {
     Tick,
     Terminal(CrosstermEvent),
+    Task(TaskEvent),
 }
 
 #[allow(dead_code)]
@@ -28,6 +31,7 @@ impl EventHandler {
         // Create message channel
         let (tx, rx) = mpsc::unbounded_channel();
         let tx2 = tx.clone();
+        TaskManager::get_instance().set_sender(tx.clone());
 
         // Create async task
         let handler = tokio::spawn(async move {
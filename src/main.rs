@@ -1,18 +1,30 @@
 mod app;
+mod cli;
 mod components;
 mod config;
 mod event;
+mod task;
 mod utils;
 
-use std::panic;
+use std::{panic, process::ExitCode};
 
 use anyhow::Result;
 use app::App;
+use clap::Parser;
+use cli::Cli;
 use components::{Component, Root};
+use config::profile::ProfileManager;
 use event::{Event, EventHandler};
+use utils::logger::Logger;
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> Result<ExitCode> {
+    let cli = Cli::parse();
+    if let Some(command) = cli.command {
+        env_logger::init();
+        return Ok(cli::run(command, cli.json).await);
+    }
+
     // Create terminal
     let mut terminal = ratatui::init();
     terminal.clear()?;
@@ -30,6 +42,11 @@ async fn main() -> Result<()> {
     // Create root component
     let mut root = Root::new();
 
+    // Subscriptions running out are worth noticing before anything else
+    for warning in ProfileManager::get_warnings() {
+        Logger::get_instance().lock().unwrap().warn(warning);
+    }
+
     // Run application
     while *App::get_instance().running.lock().unwrap() {
         terminal.draw(|frame| root.render(&frame.area(), frame))?;
@@ -37,10 +54,11 @@ async fn main() -> Result<()> {
         match event_handler.next().await? {
             Event::Tick => root.tick().await?,
             Event::Terminal(ev) => root.handle_event(&ev).await?,
+            Event::Task(ev) => root.handle_task(&ev).await?,
         }
     }
 
     // Exit application
     ratatui::restore();
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }
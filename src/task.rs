@@ -0,0 +1,148 @@
+use std::{
+    any::Any,
+    future::Future,
+    sync::{Mutex, OnceLock},
+    time::Instant,
+};
+
+use anyhow::{anyhow, Result};
+use tokio::{sync::mpsc::UnboundedSender, task::AbortHandle};
+
+use crate::event::Event;
+
+pub type TaskOutput = Box<dyn Any + Send>;
+
+pub struct TaskEvent {
+    pub id: u64,
+    pub name: String,
+    pub result: Result<TaskOutput>,
+}
+
+impl TaskEvent {
+    pub fn output<T>(&self) -> Result<&T>
+    where
+        T: 'static,
+    {
+        match &self.result {
+            Ok(output) => output
+                .downcast_ref::<T>()
+                .ok_or(anyhow!("unexpected output of task \"{}\"", self.name)),
+            Err(err) => Err(anyhow!("{:#}", err)),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TaskInfo {
+    pub id: u64,
+    pub name: String,
+    pub started_at: Instant,
+}
+
+pub struct TaskManager {
+    sender: OnceLock<UnboundedSender<Event>>,
+    state: Mutex<TaskManagerState>,
+}
+
+struct TaskManagerState {
+    next_id: u64,
+    tasks: Vec<(TaskInfo, AbortHandle)>,
+}
+
+impl TaskManager {
+    pub fn get_instance() -> &'static TaskManager {
+        static INSTANCE: OnceLock<TaskManager> = OnceLock::new();
+        INSTANCE.get_or_init(|| Self {
+            sender: OnceLock::new(),
+            state: Mutex::new(TaskManagerState {
+                next_id: 0,
+                tasks: Vec::new(),
+            }),
+        })
+    }
+
+    pub fn set_sender(&self, sender: UnboundedSender<Event>) {
+        let _ = self.sender.set(sender);
+    }
+
+    /// Runs a job in the background, its output is sent back as `Event::Task`
+    pub fn spawn<S, F, T>(&self, name: S, future: F) -> u64
+    where
+        S: Into<String>,
+        F: Future<Output = Result<T>> + Send + 'static,
+        T: Send + 'static,
+    {
+        let name = name.into();
+
+        // Registering under the lock keeps fast jobs from finishing unseen
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+
+        let task_name = name.clone();
+        let handle = tokio::spawn(async move {
+            let result = future.await.map(|v| Box::new(v) as TaskOutput);
+            let manager = TaskManager::get_instance();
+
+            if manager.remove(id) {
+                manager.send(TaskEvent {
+                    id,
+                    name: task_name,
+                    result,
+                });
+            }
+        });
+
+        state.tasks.push((
+            TaskInfo {
+                id,
+                name,
+                started_at: Instant::now(),
+            },
+            handle.abort_handle(),
+        ));
+
+        id
+    }
+
+    pub fn cancel(&self, id: u64) {
+        let task = {
+            let mut state = self.state.lock().unwrap();
+            let index = state.tasks.iter().position(|(info, _)| info.id == id);
+            index.map(|index| state.tasks.remove(index))
+        };
+
+        if let Some((info, handle)) = task {
+            handle.abort();
+            self.send(TaskEvent {
+                id,
+                name: info.name.clone(),
+                result: Err(anyhow!("task \"{}\" cancelled", info.name)),
+            });
+        }
+    }
+
+    pub fn get_tasks(&self) -> Vec<TaskInfo> {
+        self.state
+            .lock()
+            .unwrap()
+            .tasks
+            .iter()
+            .map(|(info, _)| info.clone())
+            .collect()
+    }
+
+    fn remove(&self, id: u64) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let len = state.tasks.len();
+        state.tasks.retain(|(info, _)| info.id != id);
+
+        state.tasks.len() != len
+    }
+
+    fn send(&self, ev: TaskEvent) {
+        if let Some(sender) = self.sender.get() {
+            let _ = sender.send(Event::Task(ev));
+        }
+    }
+}
@@ -1,40 +1,78 @@
 #![allow(dead_code)]
 
-use std::collections::BTreeMap;
-
-use anyhow::Result;
-use reqwest::{Client, Method, RequestBuilder};
+pub mod error;
+#[cfg(test)]
+pub mod mock;
+pub mod models;
+pub mod stream;
+#[cfg(test)]
+mod tests;
+pub mod tls;
+pub mod transport;
+
+use std::{collections::BTreeMap, time::Duration};
+
+use reqwest::{Method, Response};
 use serde::Deserialize;
-use serde_json::Value;
+use serde_json::{json, Value};
+
+use error::{MihomoApiError, Result};
+use models::{Config, Connections, DnsResponse, Proxy, ProxyProvider, Rule, RuleProvider, Version};
+use tls::TlsOptions;
+use transport::Transport;
+
+const RETRY_BACKOFF: Duration = Duration::from_millis(200);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MihomoApiOptions {
+    pub connect_timeout: Duration,
+    pub request_timeout: Duration,
+    /// Extra attempts for GET requests failing before reaching the core
+    pub retries: u32,
+    pub tls: TlsOptions,
+}
+
+impl Default for MihomoApiOptions {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(3),
+            request_timeout: Duration::from_secs(10),
+            retries: 2,
+            tls: TlsOptions::default(),
+        }
+    }
+}
 
+#[derive(Clone)]
 pub struct MihomoApi {
-    api: String,
+    transport: Transport,
     secret: Option<String>,
+    options: MihomoApiOptions,
 }
 
 impl MihomoApi {
-    pub fn new<S>(api: S, secret: Option<S>) -> Self
+    pub fn new<S>(api: S, secret: Option<S>, options: MihomoApiOptions) -> Self
     where
         S: Into<String>,
     {
         Self {
-            api: api.into(),
+            transport: Transport::new(&api.into(), options.connect_timeout, &options.tls),
             secret: secret.map(|v| v.into()),
+            options,
         }
     }
 
-    fn create_request_builder(&self, method: Method, path: &str) -> RequestBuilder {
-        let builder = Client::new().request(method, format!("{}{}", self.api, path));
-
-        match &self.secret {
-            Some(token) => builder.bearer_auth(token),
-            None => builder,
+    fn create_request_builder(&self, method: Method, path: &str) -> ApiRequest<'_> {
+        ApiRequest {
+            api: self,
+            method,
+            path: path.to_owned(),
+            body: None,
+            streaming: false,
         }
     }
 
-    // Logs
-    // Traffic
-    // Memory
+    // Logs, traffic and memory are streamed, see `stream`
 
     pub async fn get_version(&self) -> Result<String> {
         let body = self
@@ -44,12 +82,7 @@ impl MihomoApi {
             .text()
             .await?;
 
-        #[derive(Deserialize)]
-        struct Body {
-            version: String,
-        }
-
-        let body = serde_json::from_str::<Body>(&body)?;
+        let body = serde_json::from_str::<Version>(&body)?;
         Ok(body.version)
     }
 
@@ -65,7 +98,7 @@ impl MihomoApi {
 
     // Running configuration
 
-    pub async fn get_configs(&self) -> Result<Value> {
+    pub async fn get_configs(&self) -> Result<Config> {
         let body = self
             .create_request_builder(Method::GET, "/configs")
             .send()
@@ -130,7 +163,7 @@ impl MihomoApi {
 
     // Policy groups
 
-    pub async fn get_groups(&self) -> Result<Vec<Value>> {
+    pub async fn get_groups(&self) -> Result<Vec<Proxy>> {
         let body = self
             .create_request_builder(Method::GET, "/group")
             .send()
@@ -140,14 +173,14 @@ impl MihomoApi {
 
         #[derive(Deserialize)]
         struct Body {
-            proxies: Vec<Value>,
+            proxies: Vec<Proxy>,
         }
 
         let body = serde_json::from_str::<Body>(&body)?;
         Ok(body.proxies)
     }
 
-    pub async fn get_group_by_name(&self, name: &str) -> Result<Value> {
+    pub async fn get_group_by_name(&self, name: &str) -> Result<Proxy> {
         let body = self
             .create_request_builder(
                 Method::GET,
@@ -198,7 +231,7 @@ impl MihomoApi {
 
     // Proxies
 
-    pub async fn get_proxies(&self) -> Result<BTreeMap<String, Value>> {
+    pub async fn get_proxies(&self) -> Result<BTreeMap<String, Proxy>> {
         let body = self
             .create_request_builder(Method::GET, "/proxies")
             .send()
@@ -208,14 +241,14 @@ impl MihomoApi {
 
         #[derive(Deserialize)]
         struct Body {
-            proxies: BTreeMap<String, Value>,
+            proxies: BTreeMap<String, Proxy>,
         }
 
         let body = serde_json::from_str::<Body>(&body)?;
         Ok(body.proxies)
     }
 
-    pub async fn get_proxy(&self, name: &str) -> Result<Value> {
+    pub async fn get_proxy(&self, name: &str) -> Result<Proxy> {
         let body = self
             .create_request_builder(
                 Method::GET,
@@ -234,7 +267,7 @@ impl MihomoApi {
             Method::PUT,
             &format!("/proxies/{}", urlencoding::encode(name)),
         )
-        .body(format!("{{\"name\":\"{}\"}}", selection))
+        .body(json!({ "name": selection }).to_string())
         .send()
         .await?;
 
@@ -279,7 +312,7 @@ impl MihomoApi {
 
     // Proxy sets
 
-    pub async fn get_proxy_sets(&self) -> Result<BTreeMap<String, Value>> {
+    pub async fn get_proxy_sets(&self) -> Result<BTreeMap<String, ProxyProvider>> {
         let body = self
             .create_request_builder(Method::GET, "/providers/proxies")
             .send()
@@ -289,14 +322,14 @@ impl MihomoApi {
 
         #[derive(Deserialize)]
         struct Body {
-            providers: BTreeMap<String, Value>,
+            providers: BTreeMap<String, ProxyProvider>,
         }
 
         let body = serde_json::from_str::<Body>(&body)?;
         Ok(body.providers)
     }
 
-    pub async fn get_proxy_set(&self, name: &str) -> Result<Value> {
+    pub async fn get_proxy_set(&self, name: &str) -> Result<ProxyProvider> {
         let body = self
             .create_request_builder(
                 Method::GET,
@@ -321,13 +354,36 @@ impl MihomoApi {
         Ok(())
     }
 
-    pub async fn health_check_provider_proxy(&self, name: &str) -> Result<Value> {
+    pub async fn health_check_provider_proxy(&self, name: &str) -> Result<()> {
+        self.create_request_builder(
+            Method::GET,
+            &format!(
+                "/providers/proxies/{}/healthcheck",
+                urlencoding::encode(name)
+            ),
+        )
+        .send()
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn test_provider_proxy_delay(
+        &self,
+        provider: &str,
+        name: &str,
+        url: &str,
+        timeout: u64,
+    ) -> Result<u64> {
         let body = self
             .create_request_builder(
                 Method::GET,
                 &format!(
-                    "/providers/proxies/{}/healthcheck",
-                    urlencoding::encode(name)
+                    "/providers/proxies/{}/{}/healthcheck?url={}&timeout={}",
+                    urlencoding::encode(provider),
+                    urlencoding::encode(name),
+                    urlencoding::encode(url),
+                    timeout
                 ),
             )
             .send()
@@ -335,14 +391,18 @@ impl MihomoApi {
             .text()
             .await?;
 
-        Ok(serde_json::from_str(&body)?)
-    }
+        #[derive(Deserialize)]
+        struct Body {
+            delay: u64,
+        }
 
-    // providers/proxies/providers_name/proxies_name/healthcheck
+        let body = serde_json::from_str::<Body>(&body)?;
+        Ok(body.delay)
+    }
 
     // Rules
 
-    pub async fn get_rules(&self) -> Result<Vec<Value>> {
+    pub async fn get_rules(&self) -> Result<Vec<Rule>> {
         let body = self
             .create_request_builder(Method::GET, "/rules")
             .send()
@@ -352,7 +412,7 @@ impl MihomoApi {
 
         #[derive(Deserialize)]
         struct Body {
-            rules: Vec<Value>,
+            rules: Vec<Rule>,
         }
 
         let body = serde_json::from_str::<Body>(&body)?;
@@ -361,7 +421,7 @@ impl MihomoApi {
 
     // Rule sets
 
-    pub async fn get_rule_sets(&self) -> Result<BTreeMap<String, Value>> {
+    pub async fn get_rule_sets(&self) -> Result<BTreeMap<String, RuleProvider>> {
         let body = self
             .create_request_builder(Method::GET, "/providers/rules")
             .send()
@@ -371,7 +431,7 @@ impl MihomoApi {
 
         #[derive(Deserialize)]
         struct Body {
-            providers: BTreeMap<String, Value>,
+            providers: BTreeMap<String, RuleProvider>,
         }
 
         let body = serde_json::from_str::<Body>(&body)?;
@@ -391,7 +451,7 @@ impl MihomoApi {
 
     // Connections
 
-    pub async fn get_connections(&self) -> Result<Value> {
+    pub async fn get_connections(&self) -> Result<Connections> {
         let body = self
             .create_request_builder(Method::GET, "/connections")
             .send()
@@ -413,7 +473,7 @@ impl MihomoApi {
     pub async fn close_connection(&self, id: &str) -> Result<()> {
         self.create_request_builder(
             Method::DELETE,
-            &format!("/connections/{}", urlencoding::encode(&id)),
+            &format!("/connections/{}", urlencoding::encode(id)),
         )
         .send()
         .await?;
@@ -421,9 +481,9 @@ impl MihomoApi {
         Ok(())
     }
 
-    /// DNS
+    // DNS
 
-    pub async fn query_dns(&self, hostname: &str, record_type: &str) -> Result<Value> {
+    pub async fn query_dns(&self, hostname: &str, record_type: &str) -> Result<DnsResponse> {
         let body = self
             .create_request_builder(
                 Method::GET,
@@ -451,5 +511,80 @@ impl MihomoApi {
         Ok(())
     }
 
-    // debug/pprof
+    /// Text dump of a Go runtime profile such as `heap` or `goroutine`,
+    /// only served while the core runs with `log-level: debug`
+    pub async fn get_pprof(&self, profile: &str) -> Result<String> {
+        Ok(self
+            .create_request_builder(
+                Method::GET,
+                &format!("/debug/pprof/{}?debug=1", urlencoding::encode(profile)),
+            )
+            .send()
+            .await?
+            .text()
+            .await?)
+    }
+}
+
+struct ApiRequest<'a> {
+    api: &'a MihomoApi,
+    method: Method,
+    path: String,
+    body: Option<String>,
+    streaming: bool,
+}
+
+impl ApiRequest<'_> {
+    fn body(mut self, body: String) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    /// Keeps the response body open, no timeout and no retry
+    fn streaming(mut self) -> Self {
+        self.streaming = true;
+        self
+    }
+
+    /// Sends the request, non-2xx responses are turned into errors
+    async fn send(self) -> Result<Response> {
+        let options = &self.api.options;
+        let (timeout, retries) = match (self.streaming, &self.method) {
+            (true, _) => (None, 0),
+            (false, &Method::GET) => (Some(options.request_timeout), options.retries),
+            (false, _) => (Some(options.request_timeout), 0),
+        };
+
+        let mut attempt = 0;
+        let response = loop {
+            let result = self
+                .api
+                .transport
+                .send(
+                    self.method.clone(),
+                    &self.path,
+                    self.api.secret.as_deref(),
+                    self.body.clone(),
+                    timeout,
+                )
+                .await;
+
+            // Only transport failures are retried, errors from the core are final
+            match result {
+                Err(_) if attempt < retries => {
+                    tokio::time::sleep(RETRY_BACKOFF * 2u32.pow(attempt)).await;
+                    attempt += 1;
+                }
+                result => break result?,
+            }
+        };
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let body = response.text().await.unwrap_or_default();
+        Err(MihomoApiError::from_response(status, &body))
+    }
 }
@@ -0,0 +1,86 @@
+use reqwest::StatusCode;
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, MihomoApiError>;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug, Error)]
+pub enum MihomoApiError {
+    #[error("unauthorized, check the controller secret")]
+    Unauthorized,
+    #[error("not found: {0}")]
+    NotFound(String),
+    #[error("bad request: {0}")]
+    BadRequest(String),
+    #[error("request to the controller timed out")]
+    Timeout,
+    #[error("connection to the controller refused, is mihomo running?")]
+    ConnectionRefused(#[source] BoxError),
+    #[error("controller responded with {status}: {message}")]
+    Status { status: StatusCode, message: String },
+    #[error("request to the controller failed")]
+    Request(#[source] BoxError),
+    #[error("invalid TLS settings: {0}")]
+    Tls(String),
+    #[error("TLS handshake with the controller failed")]
+    Handshake(#[source] BoxError),
+    #[error("unexpected response from the controller")]
+    Json(#[from] serde_json::Error),
+}
+
+impl MihomoApiError {
+    /// Maps a non-2xx response, `body` is mihomo's `{"message": ...}` if any
+    pub fn from_response(status: StatusCode, body: &str) -> Self {
+        #[derive(serde::Deserialize)]
+        struct Body {
+            message: String,
+        }
+
+        let message = serde_json::from_str::<Body>(body)
+            .map(|b| b.message)
+            .unwrap_or_else(|_| body.trim().to_owned());
+
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Self::Unauthorized,
+            StatusCode::NOT_FOUND => Self::NotFound(message),
+            StatusCode::BAD_REQUEST => Self::BadRequest(message),
+            StatusCode::REQUEST_TIMEOUT | StatusCode::GATEWAY_TIMEOUT => Self::Timeout,
+            status => Self::Status { status, message },
+        }
+    }
+}
+
+impl From<reqwest::Error> for MihomoApiError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            Self::Timeout
+        } else if is_tls_error(&err) {
+            Self::Handshake(err.into())
+        } else if err.is_connect() {
+            Self::ConnectionRefused(err.into())
+        } else {
+            Self::Request(err.into())
+        }
+    }
+}
+
+fn is_tls_error(err: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(err);
+
+    while let Some(err) = source {
+        if err.is::<rustls::Error>() {
+            return true;
+        }
+
+        // IO errors wrap their cause without exposing it as a source
+        source = match err.downcast_ref::<std::io::Error>() {
+            Some(err) => err
+                .get_ref()
+                .map(|e| e as &(dyn std::error::Error + 'static)),
+            None => err.source(),
+        };
+    }
+
+    false
+}
@@ -0,0 +1,586 @@
+//! In-process stand-in for a mihomo controller, used by the tests
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, Request, State},
+    http::{header::AUTHORIZATION, Method, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+use serde_json::{json, Value};
+use tokio::{net::TcpListener, task::JoinHandle};
+
+use super::{MihomoApi, MihomoApiOptions};
+
+type Shared = Arc<Mutex<MockState>>;
+
+/// Everything the fake core knows, tests read and change it freely
+pub struct MockState {
+    pub secret: Option<String>,
+    pub version: String,
+    pub config: Value,
+    /// Proxies and groups by name, in the JSON shape of `/proxies`
+    pub proxies: BTreeMap<String, Value>,
+    pub providers: BTreeMap<String, Value>,
+    pub rules: Vec<Value>,
+    pub rule_providers: BTreeMap<String, Value>,
+    pub connections: Vec<Value>,
+    pub delay: u64,
+    /// Body of the last `PUT /configs`
+    pub loaded_config: Option<Value>,
+    pub restarts: usize,
+    pub fake_ip_flushes: usize,
+    /// `"METHOD /path"` of every request, query strings left out
+    pub requests: Vec<String>,
+    /// One-shot errors by `"METHOD /path"`, consumed in order
+    pub failures: HashMap<String, VecDeque<(StatusCode, String)>>,
+    /// Delay before every response, to provoke timeouts
+    pub stall: Option<Duration>,
+}
+
+impl Default for MockState {
+    fn default() -> Self {
+        let proxies = [
+            proxy("DIRECT", "Direct", None),
+            proxy("REJECT", "Reject", None),
+            proxy("node-a", "Shadowsocks", None),
+            proxy("node-b", "Vmess", Some("subscription")),
+            group("auto", "URLTest", &["node-a", "node-b"]),
+            group("Proxy", "Selector", &["auto", "node-a", "node-b", "DIRECT"]),
+            group("GLOBAL", "Selector", &["Proxy", "DIRECT", "REJECT"]),
+        ]
+        .into_iter()
+        .map(|p| (p["name"].as_str().unwrap().to_owned(), p))
+        .collect::<BTreeMap<String, Value>>();
+
+        let providers = BTreeMap::from([(
+            "subscription".to_owned(),
+            json!({
+                "name": "subscription",
+                "type": "Proxy",
+                "vehicleType": "HTTP",
+                "proxies": [proxies["node-b"]],
+                "testUrl": "https://www.gstatic.com/generate_204",
+                "updatedAt": "2024-12-01T00:00:00Z",
+                "subscriptionInfo": {
+                    "Upload": 1024,
+                    "Download": 2048,
+                    "Total": 1073741824,
+                    "Expire": 1767225600
+                }
+            }),
+        )]);
+
+        Self {
+            secret: None,
+            version: "v1.19.0".into(),
+            config: json!({
+                "port": 0,
+                "socks-port": 0,
+                "mixed-port": 7890,
+                "allow-lan": false,
+                "bind-address": "*",
+                "mode": "rule",
+                "log-level": "info",
+                "ipv6": false,
+                "tun": { "enable": false, "device": "", "stack": "gvisor" }
+            }),
+            proxies,
+            providers,
+            rules: vec![
+                json!({ "type": "DomainSuffix", "payload": "example.com", "proxy": "Proxy", "size": -1 }),
+                json!({ "type": "RuleSet", "payload": "ads", "proxy": "REJECT", "size": 2 }),
+                json!({ "type": "Match", "payload": "", "proxy": "DIRECT", "size": -1 }),
+            ],
+            rule_providers: BTreeMap::from([(
+                "ads".to_owned(),
+                json!({
+                    "name": "ads",
+                    "behavior": "Domain",
+                    "format": "YamlRule",
+                    "vehicleType": "HTTP",
+                    "ruleCount": 2,
+                    "updatedAt": "2024-12-01T00:00:00Z"
+                }),
+            )]),
+            connections: vec![
+                connection("c1", "example.com"),
+                connection("c2", "ads.test"),
+            ],
+            delay: 42,
+            loaded_config: None,
+            restarts: 0,
+            fake_ip_flushes: 0,
+            requests: Vec::new(),
+            failures: HashMap::new(),
+            stall: None,
+        }
+    }
+}
+
+pub struct MockController {
+    pub state: Shared,
+    pub url: String,
+    server: JoinHandle<()>,
+}
+
+impl MockController {
+    pub async fn start() -> Self {
+        Self::start_with(MockState::default()).await
+    }
+
+    pub async fn start_with(state: MockState) -> Self {
+        let state = Arc::new(Mutex::new(state));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let app = router(state.clone());
+        let server = tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        Self { state, url, server }
+    }
+
+    /// A client with the mock's secret and short timeouts
+    pub fn api(&self) -> MihomoApi {
+        let secret = self.state().secret.clone();
+        MihomoApi::new(
+            self.url.clone(),
+            secret,
+            MihomoApiOptions {
+                connect_timeout: Duration::from_secs(1),
+                request_timeout: Duration::from_secs(2),
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn state(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state.lock().unwrap()
+    }
+
+    /// Makes the next `method path` request fail with mihomo's error body
+    pub fn fail_next(&self, method: Method, path: &str, status: StatusCode, message: &str) {
+        self.state()
+            .failures
+            .entry(format!("{} {}", method, path))
+            .or_default()
+            .push_back((status, message.to_owned()));
+    }
+}
+
+impl Drop for MockController {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+fn router(state: Shared) -> Router {
+    Router::new()
+        .route("/version", get(version))
+        .route(
+            "/configs",
+            get(get_configs).put(put_configs).patch(patch_configs),
+        )
+        .route("/configs/geo", post(no_content))
+        .route("/restart", post(restart))
+        .route("/upgrade", post(no_content))
+        .route("/upgrade/ui", post(no_content))
+        .route("/cache/fakeip/flush", post(flush_fake_ip))
+        .route("/group", get(get_groups))
+        .route("/group/:name", get(get_group).delete(clear_group))
+        .route("/group/:name/delay", get(test_group))
+        .route("/proxies", get(get_proxies))
+        .route(
+            "/proxies/:name",
+            get(get_proxy).put(select_proxy).delete(clear_group),
+        )
+        .route("/proxies/:name/delay", get(test_proxy))
+        .route("/providers/proxies", get(get_providers))
+        .route(
+            "/providers/proxies/:name",
+            get(get_provider).put(update_provider),
+        )
+        .route("/providers/proxies/:name/healthcheck", get(check_provider))
+        .route(
+            "/providers/proxies/:provider/:name/healthcheck",
+            get(check_provider_proxy),
+        )
+        .route("/rules", get(get_rules))
+        .route("/providers/rules", get(get_rule_providers))
+        .route("/providers/rules/:name", put(update_rule_provider))
+        .route(
+            "/connections",
+            get(get_connections).delete(close_connections),
+        )
+        .route("/connections/:id", delete(close_connection))
+        .route("/dns/query", get(query_dns))
+        .route("/debug/gc", put(no_content))
+        .route("/debug/pprof/:profile", get(pprof))
+        .route("/traffic", get(traffic))
+        .route("/memory", get(memory))
+        .fallback(|| async { error(StatusCode::NOT_FOUND, "Resource not found") })
+        .layer(middleware::from_fn_with_state(state.clone(), intercept))
+        .with_state(state)
+}
+
+/// Records the request, then applies auth, stalls and scripted failures
+async fn intercept(State(state): State<Shared>, request: Request, next: Next) -> Response {
+    let key = format!("{} {}", request.method(), request.uri().path());
+
+    let (secret, stall, failure) = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(key.clone());
+        let failure = state.failures.get_mut(&key).and_then(|f| f.pop_front());
+        (state.secret.clone(), state.stall, failure)
+    };
+
+    if let Some(secret) = secret {
+        let expected = format!("Bearer {}", secret);
+        let authorized = request
+            .headers()
+            .get(AUTHORIZATION)
+            .is_some_and(|v| v.as_bytes() == expected.as_bytes());
+        if !authorized {
+            return error(StatusCode::UNAUTHORIZED, "Unauthorized");
+        }
+    }
+    if let Some(stall) = stall {
+        tokio::time::sleep(stall).await;
+    }
+    if let Some((status, message)) = failure {
+        return error(status, &message);
+    }
+
+    next.run(request).await
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "message": message }))).into_response()
+}
+
+async fn no_content() -> StatusCode {
+    StatusCode::NO_CONTENT
+}
+
+async fn version(State(state): State<Shared>) -> Json<Value> {
+    let state = state.lock().unwrap();
+    Json(json!({ "version": state.version, "meta": true }))
+}
+
+async fn get_configs(State(state): State<Shared>) -> Json<Value> {
+    Json(state.lock().unwrap().config.clone())
+}
+
+// Bodies are decoded by hand, mihomo does not require a JSON content type
+
+async fn put_configs(State(state): State<Shared>, body: String) -> Response {
+    match serde_json::from_str(&body) {
+        Ok(body) => {
+            state.lock().unwrap().loaded_config = Some(body);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(_) => error(StatusCode::BAD_REQUEST, "Body invalid"),
+    }
+}
+
+async fn patch_configs(State(state): State<Shared>, body: String) -> Response {
+    let body = serde_json::from_str::<Value>(&body).unwrap_or_default();
+    let Some(patch) = body.as_object() else {
+        return error(StatusCode::BAD_REQUEST, "Body invalid");
+    };
+
+    let mut state = state.lock().unwrap();
+    for (key, value) in patch {
+        state.config[key] = value.clone();
+    }
+
+    StatusCode::NO_CONTENT.into_response()
+}
+
+async fn restart(State(state): State<Shared>) -> Json<Value> {
+    state.lock().unwrap().restarts += 1;
+    Json(json!({ "status": "ok" }))
+}
+
+async fn flush_fake_ip(State(state): State<Shared>) -> StatusCode {
+    state.lock().unwrap().fake_ip_flushes += 1;
+    StatusCode::NO_CONTENT
+}
+
+async fn get_groups(State(state): State<Shared>) -> Json<Value> {
+    let state = state.lock().unwrap();
+    let groups = state
+        .proxies
+        .values()
+        .filter(|p| p.get("all").is_some())
+        .cloned()
+        .collect::<Vec<Value>>();
+
+    Json(json!({ "proxies": groups }))
+}
+
+async fn get_group(State(state): State<Shared>, Path(name): Path<String>) -> Response {
+    match state.lock().unwrap().proxies.get(&name) {
+        Some(group) if group.get("all").is_some() => Json(group.clone()).into_response(),
+        _ => error(StatusCode::NOT_FOUND, "Resource not found"),
+    }
+}
+
+async fn clear_group(State(state): State<Shared>, Path(name): Path<String>) -> Response {
+    let mut state = state.lock().unwrap();
+    let Some(group) = state.proxies.get_mut(&name) else {
+        return error(StatusCode::NOT_FOUND, "Resource not found");
+    };
+
+    if let Some(first) = group["all"].get(0).cloned() {
+        group["now"] = first;
+    }
+    StatusCode::NO_CONTENT.into_response()
+}
+
+async fn test_group(State(state): State<Shared>, Path(name): Path<String>) -> Response {
+    let state = state.lock().unwrap();
+    let Some(group) = state.proxies.get(&name) else {
+        return error(StatusCode::NOT_FOUND, "Resource not found");
+    };
+
+    let delays = group["all"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|m| m.as_str())
+        .map(|m| (m.to_owned(), json!(state.delay)))
+        .collect::<serde_json::Map<String, Value>>();
+
+    Json(Value::Object(delays)).into_response()
+}
+
+async fn get_proxies(State(state): State<Shared>) -> Json<Value> {
+    Json(json!({ "proxies": state.lock().unwrap().proxies }))
+}
+
+async fn get_proxy(State(state): State<Shared>, Path(name): Path<String>) -> Response {
+    match state.lock().unwrap().proxies.get(&name) {
+        Some(proxy) => Json(proxy.clone()).into_response(),
+        None => error(StatusCode::NOT_FOUND, "Resource not found"),
+    }
+}
+
+/// Mirrors the checks of mihomo's `updateProxy`
+async fn select_proxy(
+    State(state): State<Shared>,
+    Path(name): Path<String>,
+    body: String,
+) -> Response {
+    let Some(selection) = serde_json::from_str::<Value>(&body)
+        .ok()
+        .and_then(|v| v["name"].as_str().map(|s| s.to_owned()))
+    else {
+        return error(StatusCode::BAD_REQUEST, "Body invalid");
+    };
+
+    let mut state = state.lock().unwrap();
+    let Some(group) = state.proxies.get_mut(&name) else {
+        return error(StatusCode::NOT_FOUND, "Resource not found");
+    };
+    if group["type"] != "Selector" {
+        return error(StatusCode::BAD_REQUEST, "Must be a Selector");
+    }
+    let known = group["all"]
+        .as_array()
+        .is_some_and(|all| all.iter().any(|m| *m == selection));
+    if !known {
+        return error(
+            StatusCode::BAD_REQUEST,
+            &format!("Selector update error: proxy {} not found", selection),
+        );
+    }
+
+    group["now"] = json!(selection);
+    StatusCode::NO_CONTENT.into_response()
+}
+
+async fn test_proxy(State(state): State<Shared>, Path(name): Path<String>) -> Response {
+    let mut state = state.lock().unwrap();
+    let delay = state.delay;
+    let Some(proxy) = state.proxies.get_mut(&name) else {
+        return error(StatusCode::NOT_FOUND, "Resource not found");
+    };
+
+    if delay == 0 {
+        return error(StatusCode::GATEWAY_TIMEOUT, "Timeout");
+    }
+    if let Some(history) = proxy["history"].as_array_mut() {
+        history.push(json!({ "time": "2024-12-01T00:00:00Z", "delay": delay }));
+    }
+
+    Json(json!({ "delay": delay })).into_response()
+}
+
+async fn get_providers(State(state): State<Shared>) -> Json<Value> {
+    Json(json!({ "providers": state.lock().unwrap().providers }))
+}
+
+async fn get_provider(State(state): State<Shared>, Path(name): Path<String>) -> Response {
+    match state.lock().unwrap().providers.get(&name) {
+        Some(provider) => Json(provider.clone()).into_response(),
+        None => error(StatusCode::NOT_FOUND, "Resource not found"),
+    }
+}
+
+async fn update_provider(State(state): State<Shared>, Path(name): Path<String>) -> Response {
+    if state.lock().unwrap().providers.contains_key(&name) {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        error(StatusCode::NOT_FOUND, "Resource not found")
+    }
+}
+
+async fn check_provider(State(state): State<Shared>, Path(name): Path<String>) -> Response {
+    update_provider(State(state), Path(name)).await
+}
+
+async fn check_provider_proxy(
+    State(state): State<Shared>,
+    Path((provider, name)): Path<(String, String)>,
+) -> Response {
+    let state = state.lock().unwrap();
+    let found = state.providers.get(&provider).is_some_and(|p| {
+        p["proxies"]
+            .as_array()
+            .is_some_and(|proxies| proxies.iter().any(|x| x["name"] == name.as_str()))
+    });
+
+    if found {
+        Json(json!({ "delay": state.delay })).into_response()
+    } else {
+        error(StatusCode::NOT_FOUND, "Resource not found")
+    }
+}
+
+async fn get_rules(State(state): State<Shared>) -> Json<Value> {
+    Json(json!({ "rules": state.lock().unwrap().rules }))
+}
+
+async fn get_rule_providers(State(state): State<Shared>) -> Json<Value> {
+    Json(json!({ "providers": state.lock().unwrap().rule_providers }))
+}
+
+async fn update_rule_provider(State(state): State<Shared>, Path(name): Path<String>) -> Response {
+    if state.lock().unwrap().rule_providers.contains_key(&name) {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        error(StatusCode::NOT_FOUND, "Resource not found")
+    }
+}
+
+async fn get_connections(State(state): State<Shared>) -> Json<Value> {
+    let state = state.lock().unwrap();
+    let connections = (!state.connections.is_empty()).then(|| state.connections.clone());
+
+    // An empty list is sent as `null`, like the real core does
+    Json(json!({
+        "downloadTotal": 4096,
+        "uploadTotal": 1024,
+        "memory": 33554432,
+        "connections": connections,
+    }))
+}
+
+async fn close_connections(State(state): State<Shared>) -> StatusCode {
+    state.lock().unwrap().connections.clear();
+    StatusCode::NO_CONTENT
+}
+
+async fn close_connection(State(state): State<Shared>, Path(id): Path<String>) -> StatusCode {
+    state
+        .lock()
+        .unwrap()
+        .connections
+        .retain(|c| c["id"] != id.as_str());
+    StatusCode::NO_CONTENT
+}
+
+async fn query_dns(Query(query): Query<HashMap<String, String>>) -> Response {
+    let Some(name) = query.get("name") else {
+        return error(StatusCode::BAD_REQUEST, "Body invalid");
+    };
+    let (record_type, data) = match query.get("type").map(|t| t.as_str()) {
+        None | Some("A") => (1, "198.18.0.5"),
+        Some("AAAA") => (28, "fc00::5"),
+        Some(_) => return error(StatusCode::BAD_REQUEST, "invalid query type"),
+    };
+    let fqdn = format!("{}.", name.trim_end_matches('.'));
+
+    Json(json!({
+        "Status": 0,
+        "TC": false,
+        "Question": [{ "Name": fqdn, "Qtype": record_type, "Qclass": 1 }],
+        "Answer": [{ "name": fqdn, "type": record_type, "TTL": 1, "data": data }]
+    }))
+    .into_response()
+}
+
+async fn pprof(Path(profile): Path<String>) -> Response {
+    match profile.as_str() {
+        "heap" | "goroutine" => format!("{} profile: total 1\n", profile).into_response(),
+        _ => (StatusCode::NOT_FOUND, "Unknown profile").into_response(),
+    }
+}
+
+async fn traffic() -> Response {
+    Response::new(Body::from("{\"up\":1,\"down\":2}\n{\"up\":3,\"down\":4}\n"))
+}
+
+async fn memory() -> Response {
+    Response::new(Body::from("{\"inuse\":1024,\"oslimit\":0}\n"))
+}
+
+fn proxy(name: &str, proxy_type: &str, provider: Option<&str>) -> Value {
+    json!({
+        "name": name,
+        "type": proxy_type,
+        "udp": true,
+        "alive": true,
+        "history": [],
+        "provider-name": provider.unwrap_or(""),
+    })
+}
+
+fn group(name: &str, group_type: &str, members: &[&str]) -> Value {
+    let mut group = proxy(name, group_type, None);
+    group["all"] = json!(members);
+    group["now"] = json!(members[0]);
+    group
+}
+
+fn connection(id: &str, host: &str) -> Value {
+    json!({
+        "id": id,
+        "metadata": {
+            "network": "tcp",
+            "type": "HTTP",
+            "sourceIP": "127.0.0.1",
+            "destinationIP": "",
+            "sourcePort": "50000",
+            "destinationPort": "443",
+            "host": host,
+        },
+        "upload": 10,
+        "download": 20,
+        "start": "2024-12-01T00:00:00Z",
+        "chains": ["node-a", "Proxy"],
+        "rule": "DomainSuffix",
+        "rulePayload": host,
+    })
+}
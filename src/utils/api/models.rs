@@ -0,0 +1,358 @@
+use serde::{Deserialize, Deserializer, Serialize};
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Traffic {
+    pub up: u64,
+    pub down: u64,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Memory {
+    pub inuse: u64,
+    pub oslimit: u64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Log {
+    #[serde(rename = "type")]
+    pub level: LogLevel,
+    pub payload: String,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warning,
+    Error,
+    Silent,
+    #[serde(other)]
+    Unknown,
+}
+
+impl LogLevel {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Debug => "debug",
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+            Self::Silent => "silent",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Version {
+    pub version: String,
+    pub meta: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    #[default]
+    Rule,
+    Global,
+    Direct,
+    #[serde(other)]
+    Unknown,
+}
+
+impl Mode {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Rule => "rule",
+            Self::Global => "global",
+            Self::Direct => "direct",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    pub port: u16,
+    pub socks_port: u16,
+    pub redir_port: u16,
+    pub tproxy_port: u16,
+    pub mixed_port: u16,
+    pub allow_lan: bool,
+    pub bind_address: String,
+    pub mode: Mode,
+    #[serde(deserialize_with = "tolerant_log_level")]
+    pub log_level: Option<LogLevel>,
+    pub ipv6: bool,
+    pub interface_name: String,
+    pub tun: Tun,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Tun {
+    pub enable: bool,
+    pub device: String,
+    pub stack: String,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub enum ProxyType {
+    Direct,
+    Reject,
+    RejectDrop,
+    Compatible,
+    Pass,
+    Dns,
+    Shadowsocks,
+    ShadowsocksR,
+    Snell,
+    Socks5,
+    Http,
+    Vmess,
+    Vless,
+    Trojan,
+    Hysteria,
+    Hysteria2,
+    WireGuard,
+    Tuic,
+    Ssh,
+    Mieru,
+    AnyTLS,
+    Relay,
+    Selector,
+    Fallback,
+    URLTest,
+    LoadBalance,
+    #[default]
+    #[serde(other)]
+    Unknown,
+}
+
+impl ProxyType {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Direct => "Direct",
+            Self::Reject => "Reject",
+            Self::RejectDrop => "RejectDrop",
+            Self::Compatible => "Compatible",
+            Self::Pass => "Pass",
+            Self::Dns => "Dns",
+            Self::Shadowsocks => "Shadowsocks",
+            Self::ShadowsocksR => "ShadowsocksR",
+            Self::Snell => "Snell",
+            Self::Socks5 => "Socks5",
+            Self::Http => "Http",
+            Self::Vmess => "Vmess",
+            Self::Vless => "Vless",
+            Self::Trojan => "Trojan",
+            Self::Hysteria => "Hysteria",
+            Self::Hysteria2 => "Hysteria2",
+            Self::WireGuard => "WireGuard",
+            Self::Tuic => "Tuic",
+            Self::Ssh => "Ssh",
+            Self::Mieru => "Mieru",
+            Self::AnyTLS => "AnyTLS",
+            Self::Relay => "Relay",
+            Self::Selector => "Selector",
+            Self::Fallback => "Fallback",
+            Self::URLTest => "URLTest",
+            Self::LoadBalance => "LoadBalance",
+            Self::Unknown => "Unknown",
+        }
+    }
+
+    pub const fn is_group(&self) -> bool {
+        matches!(
+            self,
+            Self::Relay | Self::Selector | Self::Fallback | Self::URLTest | Self::LoadBalance
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DelayHistory {
+    pub time: String,
+    pub delay: u64,
+}
+
+/// A proxy node, policy groups share the same shape with `all` and `now` set
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Proxy {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub proxy_type: ProxyType,
+    pub udp: bool,
+    pub xudp: bool,
+    pub tfo: bool,
+    pub alive: bool,
+    pub hidden: bool,
+    pub history: Vec<DelayHistory>,
+    pub all: Vec<String>,
+    pub now: String,
+    #[serde(rename = "testUrl")]
+    pub test_url: String,
+    #[serde(rename = "provider-name")]
+    pub provider_name: String,
+}
+
+impl Proxy {
+    pub fn last_delay(&self) -> Option<u64> {
+        self.history.last().map(|h| h.delay)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub enum VehicleType {
+    #[serde(rename = "HTTP")]
+    Http,
+    File,
+    Inline,
+    Compatible,
+    #[default]
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct SubscriptionInfo {
+    pub upload: u64,
+    pub download: u64,
+    pub total: u64,
+    pub expire: u64,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ProxyProvider {
+    pub name: String,
+    pub vehicle_type: VehicleType,
+    pub proxies: Vec<Proxy>,
+    pub test_url: String,
+    pub updated_at: String,
+    pub subscription_info: Option<SubscriptionInfo>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Rule {
+    #[serde(rename = "type")]
+    pub rule_type: String,
+    pub payload: String,
+    pub proxy: String,
+    pub size: i64,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RuleProvider {
+    pub name: String,
+    pub behavior: String,
+    pub format: String,
+    pub vehicle_type: VehicleType,
+    pub rule_count: u64,
+    pub updated_at: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Connections {
+    pub upload_total: u64,
+    pub download_total: u64,
+    pub memory: u64,
+    #[serde(deserialize_with = "nullable")]
+    pub connections: Vec<Connection>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Connection {
+    pub id: String,
+    pub metadata: Metadata,
+    pub upload: u64,
+    pub download: u64,
+    pub start: String,
+    pub chains: Vec<String>,
+    pub rule: String,
+    pub rule_payload: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Metadata {
+    pub network: String,
+    #[serde(rename = "type")]
+    pub conn_type: String,
+    #[serde(rename = "sourceIP")]
+    pub source_ip: String,
+    #[serde(rename = "destinationIP")]
+    pub destination_ip: String,
+    pub source_port: String,
+    pub destination_port: String,
+    pub host: String,
+    pub dns_mode: String,
+    pub process: String,
+    pub process_path: String,
+    pub special_proxy: String,
+    pub sniff_host: String,
+    pub inbound_name: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DnsResponse {
+    #[serde(rename = "Status")]
+    pub status: u32,
+    #[serde(rename = "TC")]
+    pub truncated: bool,
+    #[serde(rename = "Question")]
+    pub question: Vec<DnsQuestion>,
+    #[serde(rename = "Answer")]
+    pub answer: Vec<DnsAnswer>,
+    #[serde(rename = "Authority")]
+    pub authority: Vec<DnsAnswer>,
+    #[serde(rename = "Additional")]
+    pub additional: Vec<DnsAnswer>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct DnsQuestion {
+    pub name: String,
+    pub qtype: u16,
+    pub qclass: u16,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DnsAnswer {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub record_type: u16,
+    #[serde(rename = "TTL")]
+    pub ttl: u32,
+    pub data: String,
+}
+
+/// Mihomo encodes empty lists as `null`
+fn nullable<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+fn tolerant_log_level<'de, D>(deserializer: D) -> Result<Option<LogLevel>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = Option::<String>::deserialize(deserializer)?;
+    Ok(value.and_then(|v| serde_json::from_value(serde_json::Value::String(v)).ok()))
+}
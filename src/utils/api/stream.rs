@@ -0,0 +1,179 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use futures::{stream, Stream};
+use reqwest::{Method, Response};
+use serde::de::DeserializeOwned;
+use tokio::time;
+use tokio_tungstenite::tungstenite::Message;
+
+use super::{
+    models::{Log, LogLevel, Memory, Traffic},
+    transport::WebSocket,
+    MihomoApi,
+};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamTransport {
+    Http,
+    WebSocket,
+}
+
+impl MihomoApi {
+    /// Streams the realtime traffic, one item per second.
+    ///
+    /// The stream never ends: connection failures are yielded as errors and
+    /// the subscription is re-established, e.g. after the core restarts.
+    pub fn stream_traffic(
+        &self,
+        transport: StreamTransport,
+    ) -> impl Stream<Item = Result<Traffic>> {
+        self.subscribe("/traffic".into(), transport)
+    }
+
+    /// Streams the memory usage, one item per second.
+    ///
+    /// See [`MihomoApi::stream_traffic`] for the reconnection behavior.
+    pub fn stream_memory(&self, transport: StreamTransport) -> impl Stream<Item = Result<Memory>> {
+        self.subscribe("/memory".into(), transport)
+    }
+
+    /// Streams the core logs at or above the given level.
+    ///
+    /// See [`MihomoApi::stream_traffic`] for the reconnection behavior.
+    pub fn stream_logs(
+        &self,
+        level: LogLevel,
+        transport: StreamTransport,
+    ) -> impl Stream<Item = Result<Log>> {
+        self.subscribe(format!("/logs?level={}", level.as_str()), transport)
+    }
+
+    fn subscribe<T>(
+        &self,
+        path: String,
+        transport: StreamTransport,
+    ) -> impl Stream<Item = Result<T>>
+    where
+        T: DeserializeOwned,
+    {
+        let subscription = Subscription {
+            api: self.clone(),
+            path,
+            transport,
+            connection: None,
+            buffer: Vec::new(),
+            failed: false,
+        };
+
+        stream::unfold(subscription, |mut subscription| async move {
+            let item = subscription.next_item().await;
+            Some((item, subscription))
+        })
+    }
+}
+
+enum Connection {
+    Http(Response),
+    WebSocket(WebSocket),
+}
+
+struct Subscription {
+    api: MihomoApi,
+    path: String,
+    transport: StreamTransport,
+    connection: Option<Connection>,
+    buffer: Vec<u8>,
+    failed: bool,
+}
+
+impl Subscription {
+    async fn next_item<T>(&mut self) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        loop {
+            // Drain complete lines received from the chunked body first
+            if let Some(pos) = self.buffer.iter().position(|b| *b == b'\n') {
+                let line = self.buffer.drain(..=pos).collect::<Vec<u8>>();
+                if line.iter().all(|b| b.is_ascii_whitespace()) {
+                    continue;
+                }
+
+                return Ok(serde_json::from_slice(&line)?);
+            }
+
+            if self.connection.is_none() {
+                if self.failed {
+                    time::sleep(RECONNECT_DELAY).await;
+                }
+
+                match self.connect().await {
+                    Ok(connection) => {
+                        self.connection = Some(connection);
+                        self.failed = false;
+                    }
+                    Err(err) => {
+                        self.failed = true;
+                        return Err(err);
+                    }
+                }
+            }
+
+            let result = match self.connection.as_mut().unwrap() {
+                Connection::Http(response) => match response.chunk().await {
+                    Ok(Some(chunk)) => {
+                        self.buffer.extend_from_slice(&chunk);
+                        continue;
+                    }
+                    Ok(None) => Err(anyhow!("stream `{}` closed by core", self.path)),
+                    Err(err) => Err(err.into()),
+                },
+                Connection::WebSocket(ws) => match ws.next().await {
+                    Some(Ok(Message::Text(text))) => return Ok(serde_json::from_str(&text)?),
+                    Some(Ok(Message::Ping(payload))) => {
+                        let _ = ws.send(Message::Pong(payload)).await;
+                        continue;
+                    }
+                    Some(Ok(Message::Close(_))) | None => {
+                        Err(anyhow!("stream `{}` closed by core", self.path))
+                    }
+                    Some(Ok(_)) => continue,
+                    Some(Err(err)) => Err(err.into()),
+                },
+            };
+
+            // Drop the broken connection and reconnect on next poll
+            self.connection = None;
+            self.buffer.clear();
+            self.failed = true;
+            return result;
+        }
+    }
+
+    async fn connect(&self) -> Result<Connection> {
+        match self.transport {
+            StreamTransport::Http => {
+                let response = self
+                    .api
+                    .create_request_builder(Method::GET, &self.path)
+                    .streaming()
+                    .send()
+                    .await?;
+
+                Ok(Connection::Http(response))
+            }
+            StreamTransport::WebSocket => {
+                let ws = self
+                    .api
+                    .transport
+                    .connect_websocket(&self.path, self.api.secret.as_deref())
+                    .await?;
+
+                Ok(Connection::WebSocket(ws))
+            }
+        }
+    }
+}
@@ -0,0 +1,321 @@
+use std::time::Duration;
+
+use axum::http::{Method, StatusCode};
+use futures::StreamExt;
+use serde_json::json;
+
+use super::{
+    error::MihomoApiError,
+    mock::{MockController, MockState},
+    models::{Log, LogLevel, Mode, ProxyType},
+    stream::StreamTransport,
+    MihomoApi, MihomoApiOptions,
+};
+
+#[tokio::test]
+async fn reads_version_and_configs() {
+    let mock = MockController::start().await;
+    let api = mock.api();
+
+    assert_eq!(api.get_version().await.unwrap(), "v1.19.0");
+
+    let config = api.get_configs().await.unwrap();
+    assert_eq!(config.mode, Mode::Rule);
+    assert_eq!(config.mixed_port, 7890);
+}
+
+#[tokio::test]
+async fn lists_groups_and_proxies() {
+    let mock = MockController::start().await;
+    let api = mock.api();
+
+    let groups = api.get_groups().await.unwrap();
+    assert_eq!(
+        groups.iter().map(|g| g.name.as_str()).collect::<Vec<_>>(),
+        ["GLOBAL", "Proxy", "auto"]
+    );
+
+    let proxies = api.get_proxies().await.unwrap();
+    assert_eq!(proxies["node-a"].proxy_type, ProxyType::Shadowsocks);
+    assert_eq!(proxies["node-b"].provider_name, "subscription");
+    assert!(proxies["auto"].proxy_type.is_group());
+}
+
+#[tokio::test]
+async fn switches_selector() {
+    let mock = MockController::start().await;
+    let api = mock.api();
+
+    api.update_proxy("Proxy", "node-b").await.unwrap();
+    assert_eq!(api.get_proxy("Proxy").await.unwrap().now, "node-b");
+
+    api.unselct_proxy("Proxy").await.unwrap();
+    assert_eq!(api.get_group_by_name("Proxy").await.unwrap().now, "auto");
+}
+
+#[tokio::test]
+async fn rejects_invalid_switches() {
+    let mock = MockController::start().await;
+    let api = mock.api();
+
+    let err = api.update_proxy("auto", "node-b").await.unwrap_err();
+    assert!(matches!(err, MihomoApiError::BadRequest(ref m) if m == "Must be a Selector"));
+
+    let err = api.update_proxy("Proxy", "missing").await.unwrap_err();
+    assert!(matches!(err, MihomoApiError::BadRequest(_)));
+
+    // Quotes in node names stay inside the JSON string
+    let err = api.update_proxy("Proxy", "node \"a\\").await.unwrap_err();
+    assert!(matches!(err, MihomoApiError::BadRequest(ref m) if m.contains("node \"a\\ not found")));
+
+    let err = api.update_proxy("Missing", "node-a").await.unwrap_err();
+    assert!(matches!(err, MihomoApiError::NotFound(_)));
+
+    // Nothing changed on the core
+    assert_eq!(api.get_proxy("Proxy").await.unwrap().now, "auto");
+}
+
+#[tokio::test]
+async fn tests_delays() {
+    let mock = MockController::start().await;
+    let api = mock.api();
+
+    assert_eq!(
+        api.test_proxy_delay("node-a", "http://test", 1000)
+            .await
+            .unwrap(),
+        42
+    );
+    assert_eq!(
+        api.get_proxy("node-a").await.unwrap().last_delay(),
+        Some(42)
+    );
+
+    let delays = api
+        .test_group_delay("auto", "http://test", 1000)
+        .await
+        .unwrap();
+    assert_eq!(delays.len(), 2);
+
+    assert_eq!(
+        api.test_provider_proxy_delay("subscription", "node-b", "http://test", 1000)
+            .await
+            .unwrap(),
+        42
+    );
+
+    mock.state().delay = 0;
+    let err = api
+        .test_proxy_delay("node-a", "http://test", 1000)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, MihomoApiError::Timeout));
+}
+
+#[tokio::test]
+async fn patches_configs() {
+    let mock = MockController::start().await;
+    let api = mock.api();
+
+    api.patch_configs(&json!({ "mode": "global", "allow-lan": true }))
+        .await
+        .unwrap();
+
+    let config = api.get_configs().await.unwrap();
+    assert_eq!(config.mode, Mode::Global);
+    assert!(config.allow_lan);
+    assert_eq!(config.mixed_port, 7890);
+
+    let err = api.patch_configs(&json!(["mode"])).await.unwrap_err();
+    assert!(matches!(err, MihomoApiError::BadRequest(_)));
+}
+
+#[tokio::test]
+async fn reloads_and_restarts() {
+    let mock = MockController::start().await;
+    let api = mock.api();
+
+    let payload = json!({ "path": "", "payload": "mode: direct" });
+    api.update_configs(&payload).await.unwrap();
+    assert_eq!(mock.state().loaded_config, Some(payload));
+
+    api.restart().await.unwrap();
+    api.restart().await.unwrap();
+    assert_eq!(mock.state().restarts, 2);
+}
+
+#[tokio::test]
+async fn reads_providers_and_rules() {
+    let mock = MockController::start().await;
+    let api = mock.api();
+
+    let providers = api.get_proxy_sets().await.unwrap();
+    let info = providers["subscription"].subscription_info.clone().unwrap();
+    assert_eq!(info.total, 1073741824);
+    api.update_proxy_set("subscription").await.unwrap();
+    api.health_check_provider_proxy("subscription")
+        .await
+        .unwrap();
+
+    let rules = api.get_rules().await.unwrap();
+    assert_eq!(rules.len(), 3);
+    assert_eq!(rules[0].proxy, "Proxy");
+
+    let rule_sets = api.get_rule_sets().await.unwrap();
+    assert_eq!(rule_sets["ads"].rule_count, 2);
+    api.update_rule_set("ads").await.unwrap();
+
+    let err = api.update_rule_set("missing").await.unwrap_err();
+    assert!(matches!(err, MihomoApiError::NotFound(_)));
+}
+
+#[tokio::test]
+async fn closes_connections() {
+    let mock = MockController::start().await;
+    let api = mock.api();
+
+    let connections = api.get_connections().await.unwrap();
+    assert_eq!(connections.connections.len(), 2);
+    assert_eq!(connections.connections[0].metadata.host, "example.com");
+
+    api.close_connection("c1").await.unwrap();
+    assert_eq!(api.get_connections().await.unwrap().connections.len(), 1);
+
+    // The core sends `null` once the list is empty
+    api.close_all_connections().await.unwrap();
+    assert!(api.get_connections().await.unwrap().connections.is_empty());
+}
+
+#[tokio::test]
+async fn queries_dns_and_debug_endpoints() {
+    let mock = MockController::start().await;
+    let api = mock.api();
+
+    let response = api.query_dns("example.com", "A").await.unwrap();
+    assert_eq!(response.answer[0].data, "198.18.0.5");
+    assert_eq!(response.question[0].name, "example.com.");
+
+    api.clear_fake_ip_cache().await.unwrap();
+    assert_eq!(mock.state().fake_ip_flushes, 1);
+
+    api.debug_gc().await.unwrap();
+    assert!(api.get_pprof("heap").await.unwrap().starts_with("heap"));
+}
+
+#[tokio::test]
+async fn streams_traffic() {
+    let mock = MockController::start().await;
+    let api = mock.api();
+
+    let traffic = api
+        .stream_traffic(StreamTransport::Http)
+        .take(2)
+        .collect::<Vec<_>>()
+        .await;
+    let traffic = traffic
+        .into_iter()
+        .map(|t| t.unwrap().up)
+        .collect::<Vec<_>>();
+    assert_eq!(traffic, [1, 3]);
+}
+
+#[test]
+fn tolerates_unknown_log_levels() {
+    let log = serde_json::from_value::<Log>(json!({ "type": "fatal", "payload": "boom" })).unwrap();
+    assert_eq!(log.level, LogLevel::Unknown);
+    assert_eq!(log.payload, "boom");
+}
+
+#[tokio::test]
+async fn maps_auth_errors() {
+    let mock = MockController::start_with(MockState {
+        secret: Some("secret".into()),
+        ..Default::default()
+    })
+    .await;
+
+    assert!(mock.api().get_version().await.is_ok());
+
+    let api = MihomoApi::new(mock.url.clone(), None, MihomoApiOptions::default());
+    let err = api.get_version().await.unwrap_err();
+    assert!(matches!(err, MihomoApiError::Unauthorized));
+}
+
+#[tokio::test]
+async fn maps_scripted_failures() {
+    let mock = MockController::start().await;
+    let api = mock.api();
+
+    mock.fail_next(
+        Method::POST,
+        "/restart",
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "restart failed",
+    );
+    let err = api.restart().await.unwrap_err();
+    assert!(matches!(
+        err,
+        MihomoApiError::Status { status, ref message }
+            if status == StatusCode::INTERNAL_SERVER_ERROR && message == "restart failed"
+    ));
+    assert_eq!(mock.state().restarts, 0);
+
+    // Failures are one-shot
+    api.restart().await.unwrap();
+    assert_eq!(mock.state().restarts, 1);
+}
+
+#[tokio::test]
+async fn does_not_retry_core_errors() {
+    let mock = MockController::start().await;
+    let api = mock.api();
+
+    mock.fail_next(
+        Method::GET,
+        "/version",
+        StatusCode::SERVICE_UNAVAILABLE,
+        "busy",
+    );
+    assert!(api.get_version().await.is_err());
+    assert_eq!(mock.state().requests, ["GET /version"]);
+}
+
+#[tokio::test]
+async fn times_out_stalled_requests() {
+    let mock = MockController::start().await;
+    mock.state().stall = Some(Duration::from_millis(500));
+
+    let api = MihomoApi::new(
+        mock.url.clone(),
+        None,
+        MihomoApiOptions {
+            request_timeout: Duration::from_millis(100),
+            retries: 1,
+            ..Default::default()
+        },
+    );
+    let err = api.get_version().await.unwrap_err();
+    assert!(matches!(err, MihomoApiError::Timeout));
+
+    // Timeouts are transport failures, so the GET was retried once
+    assert_eq!(mock.state().requests.len(), 2);
+}
+
+#[tokio::test]
+async fn reports_unreachable_controller() {
+    let mock = MockController::start().await;
+    let url = mock.url.clone();
+    drop(mock);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let api = MihomoApi::new(
+        url,
+        None,
+        MihomoApiOptions {
+            retries: 0,
+            ..Default::default()
+        },
+    );
+    let err = api.get_version().await.unwrap_err();
+    assert!(matches!(err, MihomoApiError::ConnectionRefused(_)));
+}
@@ -0,0 +1,200 @@
+use std::{fs::File, io::BufReader, path::PathBuf, sync::Arc};
+
+use anyhow::{anyhow, bail, Context, Result};
+use ring::digest::{digest, SHA256};
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{self, CryptoProvider},
+    pki_types::{CertificateDer, ServerName, UnixTime},
+    ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+
+/// TLS settings of an https controller, all optional
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TlsOptions {
+    /// PEM bundle trusted instead of the system roots
+    pub ca_cert: Option<PathBuf>,
+    /// PEM certificate chain, the key may live in the same file
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+    /// Hex SHA-256 of the server certificate, colons are allowed
+    pub fingerprint: Option<String>,
+}
+
+impl TlsOptions {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Returns `None` when nothing is customized and the default client fits
+    pub fn build_client_config(&self) -> Result<Option<ClientConfig>> {
+        if self.is_default() {
+            return Ok(None);
+        }
+
+        let provider = Arc::new(crypto::ring::default_provider());
+        let builder = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?;
+
+        // A pinned certificate replaces chain validation, self-signed ones included
+        let builder = match &self.fingerprint {
+            Some(fingerprint) => builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(PinnedVerifier {
+                    fingerprint: parse_fingerprint(fingerprint)?,
+                    provider,
+                })),
+            None => builder.with_root_certificates(self.load_roots()?),
+        };
+
+        let config = match &self.client_cert {
+            Some(cert_path) => {
+                let certs = load_certs(cert_path)?;
+                let key_path = self.client_key.as_ref().unwrap_or(cert_path);
+                let key = rustls_pemfile::private_key(&mut open(key_path)?)
+                    .with_context(|| format!("could not read key `{}`", key_path.display()))?
+                    .ok_or(anyhow!("no private key found in `{}`", key_path.display()))?;
+
+                builder
+                    .with_client_auth_cert(certs, key)
+                    .context("invalid client certificate")?
+            }
+            None => builder.with_no_client_auth(),
+        };
+
+        Ok(Some(config))
+    }
+
+    fn load_roots(&self) -> Result<RootCertStore> {
+        let (certs, source) = match &self.ca_cert {
+            Some(path) => (load_certs(path)?, format!("`{}`", path.display())),
+            None => (
+                rustls_native_certs::load_native_certs().certs,
+                "the system store".to_owned(),
+            ),
+        };
+
+        let mut roots = RootCertStore::empty();
+        let (_, ignored) = roots.add_parsable_certificates(certs);
+        if roots.is_empty() {
+            bail!(
+                "no valid certificate found in {} ({} ignored)",
+                source,
+                ignored
+            );
+        }
+
+        Ok(roots)
+    }
+}
+
+fn open(path: &PathBuf) -> Result<BufReader<File>> {
+    let file =
+        File::open(path).with_context(|| format!("could not open file `{}`", path.display()))?;
+
+    Ok(BufReader::new(file))
+}
+
+fn load_certs(path: &PathBuf) -> Result<Vec<CertificateDer<'static>>> {
+    let certs = rustls_pemfile::certs(&mut open(path)?)
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("could not read certificates `{}`", path.display()))?;
+    if certs.is_empty() {
+        bail!("no certificate found in `{}`", path.display());
+    }
+
+    Ok(certs)
+}
+
+fn parse_fingerprint(value: &str) -> Result<Vec<u8>> {
+    let hex = value.replace(':', "").to_lowercase();
+    if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("fingerprint `{}` is not a SHA-256 hex digest", value);
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|_| anyhow!("fingerprint `{}` is not a SHA-256 hex digest", value))
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+struct PinnedVerifier {
+    fingerprint: Vec<u8>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if digest(&SHA256, end_entity).as_ref() == self.fingerprint.as_slice() {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::General(
+                "certificate fingerprint does not match the pinned one".into(),
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_fingerprint;
+
+    #[test]
+    fn parses_fingerprints() {
+        let hex = "ab".repeat(32);
+        assert_eq!(parse_fingerprint(&hex).unwrap(), vec![0xab; 32]);
+
+        let colons = vec!["AB"; 32].join(":");
+        assert_eq!(parse_fingerprint(&colons).unwrap(), vec![0xab; 32]);
+
+        // 64 bytes long, but not 64 hex digits
+        let pasted = format!("{}…", "ab".repeat(30) + "a");
+        assert_eq!(pasted.len(), 64);
+        assert!(parse_fingerprint(&pasted).is_err());
+    }
+}
@@ -0,0 +1,297 @@
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, bail};
+use futures::{SinkExt, StreamExt};
+use reqwest::{header::AUTHORIZATION, Client, Method, Response, Url};
+use rustls::{pki_types::ServerName, ClientConfig};
+use tokio::net::TcpStream;
+use tokio_rustls::{client::TlsStream, TlsConnector};
+use tokio_tungstenite::{
+    client_async, connect_async,
+    tungstenite::{self, client::IntoClientRequest, http::HeaderValue, Message},
+    MaybeTlsStream, WebSocketStream,
+};
+
+use super::{
+    error::{MihomoApiError, Result},
+    tls::TlsOptions,
+};
+
+/// How the controller is reached, either `http(s)://host:port` or
+/// `unix:///path/to/mihomo.sock` for `external-controller-unix`
+#[derive(Clone, Debug)]
+pub enum Transport {
+    Http {
+        base: String,
+        client: Client,
+        tls: Option<Arc<ClientConfig>>,
+    },
+    Unix {
+        path: PathBuf,
+        connect_timeout: Duration,
+    },
+    /// TLS settings could not be loaded, every request fails with the reason
+    Invalid(String),
+}
+
+impl Transport {
+    /// The HTTP client is pooled, clones of the transport share it
+    pub fn new(api: &str, connect_timeout: Duration, tls: &TlsOptions) -> Self {
+        if let Some(path) = api.strip_prefix("unix://") {
+            return Self::Unix {
+                path: PathBuf::from(path),
+                connect_timeout,
+            };
+        }
+
+        let tls = match tls.build_client_config() {
+            Ok(tls) => tls.map(Arc::new),
+            Err(err) => return Self::Invalid(format!("{:#}", err)),
+        };
+        let mut builder = Client::builder().connect_timeout(connect_timeout);
+        if let Some(tls) = &tls {
+            builder = builder.use_preconfigured_tls(ClientConfig::clone(tls));
+        }
+
+        match builder.build() {
+            Ok(client) => Self::Http {
+                base: api.to_owned(),
+                client,
+                tls,
+            },
+            Err(err) => Self::Invalid(err.to_string()),
+        }
+    }
+
+    /// `timeout` covers the whole exchange, streams pass `None`
+    pub async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        secret: Option<&str>,
+        body: Option<String>,
+        timeout: Option<Duration>,
+    ) -> Result<Response> {
+        match self {
+            Self::Http { base, client, .. } => {
+                let mut builder = client.request(method, format!("{}{}", base, path));
+                if let Some(timeout) = timeout {
+                    builder = builder.timeout(timeout);
+                }
+                if let Some(token) = secret {
+                    builder = builder.bearer_auth(token);
+                }
+                if let Some(body) = body {
+                    builder = builder.body(body);
+                }
+
+                Ok(builder.send().await?)
+            }
+            Self::Unix {
+                path: socket,
+                connect_timeout,
+            } => {
+                let request = unix::send(socket, *connect_timeout, method, path, secret, body);
+
+                match timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, request)
+                        .await
+                        .map_err(|_| MihomoApiError::Timeout)?,
+                    None => request.await,
+                }
+            }
+            Self::Invalid(reason) => Err(MihomoApiError::Tls(reason.clone())),
+        }
+    }
+
+    pub async fn connect_websocket(
+        &self,
+        path: &str,
+        secret: Option<&str>,
+    ) -> anyhow::Result<WebSocket> {
+        let url = match self {
+            Self::Http { base, .. } => {
+                if let Some(rest) = base.strip_prefix("https://") {
+                    format!("wss://{}{}", rest, path)
+                } else if let Some(rest) = base.strip_prefix("http://") {
+                    format!("ws://{}{}", rest, path)
+                } else {
+                    format!("{}{}", base, path)
+                }
+            }
+            // Only the path matters over a socket, the host is a placeholder
+            Self::Unix { .. } => format!("ws://localhost{}", path),
+            Self::Invalid(reason) => bail!(MihomoApiError::Tls(reason.clone())),
+        };
+
+        let secure = url.starts_with("wss://");
+        let mut request = url.into_client_request()?;
+        if let Some(token) = secret {
+            request.headers_mut().insert(
+                AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {}", token))?,
+            );
+        }
+
+        match self {
+            Self::Http {
+                tls: Some(tls),
+                base,
+                ..
+            } if secure => {
+                let url = Url::parse(base)?;
+                let host = url
+                    .host_str()
+                    .ok_or(anyhow!("missing host in `{}`", base))?;
+                let port = url.port_or_known_default().unwrap_or(443);
+
+                let stream = TcpStream::connect((host, port)).await?;
+                let stream = TlsConnector::from(tls.clone())
+                    .connect(ServerName::try_from(host.to_owned())?, stream)
+                    .await?;
+                let (ws, _) = client_async(request, stream).await?;
+
+                Ok(WebSocket::Tls(Box::new(ws)))
+            }
+            Self::Http { .. } | Self::Invalid(_) => {
+                let (ws, _) = connect_async(request).await?;
+                Ok(WebSocket::Tcp(Box::new(ws)))
+            }
+            Self::Unix {
+                path: socket,
+                connect_timeout,
+            } => unix::connect_websocket(socket, *connect_timeout, request).await,
+        }
+    }
+}
+
+pub enum WebSocket {
+    Tcp(Box<WebSocketStream<MaybeTlsStream<TcpStream>>>),
+    Tls(Box<WebSocketStream<TlsStream<TcpStream>>>),
+    #[cfg(unix)]
+    Unix(Box<WebSocketStream<tokio::net::UnixStream>>),
+}
+
+impl WebSocket {
+    pub async fn next(&mut self) -> Option<tungstenite::Result<Message>> {
+        match self {
+            Self::Tcp(ws) => ws.next().await,
+            Self::Tls(ws) => ws.next().await,
+            #[cfg(unix)]
+            Self::Unix(ws) => ws.next().await,
+        }
+    }
+
+    pub async fn send(&mut self, message: Message) -> tungstenite::Result<()> {
+        match self {
+            Self::Tcp(ws) => ws.send(message).await,
+            Self::Tls(ws) => ws.send(message).await,
+            #[cfg(unix)]
+            Self::Unix(ws) => ws.send(message).await,
+        }
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::{io::ErrorKind, path::Path, time::Duration};
+
+    use http_body_util::Full;
+    use hyper::{body::Bytes, client::conn::http1, header::HOST, Request};
+    use hyper_util::rt::TokioIo;
+    use reqwest::{header::AUTHORIZATION, Body, Method, Response};
+    use tokio::net::UnixStream;
+    use tokio_tungstenite::{client_async, tungstenite};
+
+    use super::{MihomoApiError, Result, WebSocket};
+
+    async fn connect(socket: &Path, timeout: Duration) -> Result<UnixStream> {
+        let stream = tokio::time::timeout(timeout, UnixStream::connect(socket))
+            .await
+            .map_err(|_| MihomoApiError::Timeout)?;
+
+        stream.map_err(|err| match err.kind() {
+            ErrorKind::NotFound | ErrorKind::ConnectionRefused => {
+                MihomoApiError::ConnectionRefused(err.into())
+            }
+            _ => MihomoApiError::Request(err.into()),
+        })
+    }
+
+    pub async fn send(
+        socket: &Path,
+        connect_timeout: Duration,
+        method: Method,
+        path: &str,
+        secret: Option<&str>,
+        body: Option<String>,
+    ) -> Result<Response> {
+        let stream = connect(socket, connect_timeout).await?;
+        let (mut sender, connection) = http1::handshake(TokioIo::new(stream))
+            .await
+            .map_err(|err| MihomoApiError::Request(err.into()))?;
+
+        // The connection has to be driven until the body is fully read
+        tokio::spawn(connection);
+
+        let mut builder = Request::builder()
+            .method(method)
+            .uri(path)
+            .header(HOST, "localhost");
+        if let Some(token) = secret {
+            builder = builder.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let request = builder
+            .body(Full::new(Bytes::from(body.unwrap_or_default())))
+            .map_err(|err| MihomoApiError::Request(err.into()))?;
+
+        let response = sender
+            .send_request(request)
+            .await
+            .map_err(|err| MihomoApiError::Request(err.into()))?;
+
+        Ok(Response::from(response.map(Body::wrap)))
+    }
+
+    pub async fn connect_websocket(
+        socket: &Path,
+        connect_timeout: Duration,
+        request: tungstenite::handshake::client::Request,
+    ) -> anyhow::Result<WebSocket> {
+        let stream = connect(socket, connect_timeout).await?;
+        let (ws, _) = client_async(request, stream).await?;
+
+        Ok(WebSocket::Unix(Box::new(ws)))
+    }
+}
+
+#[cfg(not(unix))]
+mod unix {
+    use std::{path::Path, time::Duration};
+
+    use reqwest::{Method, Response};
+    use tokio_tungstenite::tungstenite;
+
+    use super::{MihomoApiError, Result, WebSocket};
+
+    pub async fn send(
+        _socket: &Path,
+        _connect_timeout: Duration,
+        _method: Method,
+        _path: &str,
+        _secret: Option<&str>,
+        _body: Option<String>,
+    ) -> Result<Response> {
+        Err(MihomoApiError::Request(
+            "unix sockets are not supported on this platform".into(),
+        ))
+    }
+
+    pub async fn connect_websocket(
+        _socket: &Path,
+        _connect_timeout: Duration,
+        _request: tungstenite::handshake::client::Request,
+    ) -> anyhow::Result<WebSocket> {
+        anyhow::bail!("unix sockets are not supported on this platform")
+    }
+}
@@ -18,34 +18,14 @@ impl Logger {
         })
     }
 
-    pub fn set_buffer_size(&mut self, new_size: usize) {
-        self.buffer_size = new_size;
-
-        let buffer_len = self.buffer.len();
-        if buffer_len > new_size {
-            self.buffer.drain(0..(buffer_len - new_size));
-        }
-    }
-    pub fn get_buffer_size(&self) -> usize {
-        self.buffer_size
-    }
-
     pub fn get_buffer(&self) -> &Vec<(LogLevel, String)> {
         &self.buffer
     }
 
-    pub fn trace<S>(&mut self, text: S)
-    where
-        S: Into<String>,
-    {
-        self.push(LogLevel::Trace, text.into());
-    }
-
-    pub fn debug<S>(&mut self, text: S)
-    where
-        S: Into<String>,
-    {
-        self.push(LogLevel::Debug, text.into());
+    /// Replaces the entries as they are, for fixtures
+    #[cfg(test)]
+    pub fn set_buffer(&mut self, buffer: Vec<(LogLevel, String)>) {
+        self.buffer = buffer;
     }
 
     pub fn info<S>(&mut self, text: S)
@@ -85,8 +65,6 @@ impl Logger {
 }
 
 pub enum LogLevel {
-    Trace,
-    Debug,
     Info,
     Warn,
     Error,
@@ -95,8 +73,6 @@ pub enum LogLevel {
 impl LogLevel {
     pub const fn to_str(&self) -> &'static str {
         match self {
-            Self::Trace => "Trace",
-            Self::Debug => "Debug",
             Self::Info => " Info",
             Self::Warn => " Warn",
             Self::Error => "Error",
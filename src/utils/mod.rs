@@ -1,4 +1,8 @@
 pub mod api;
+pub mod format;
 pub mod logger;
 pub mod path;
 pub mod script;
+pub mod subscription;
+#[cfg(test)]
+pub mod testing;
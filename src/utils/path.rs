@@ -18,12 +18,23 @@ pub fn get_project_dir() -> &'static ProjectDirs {
 pub fn get_data_dir() -> &'static Path {
     static INSTANCE: OnceLock<&Path> = OnceLock::new();
     INSTANCE.get_or_init(|| {
-        let dir = get_project_dir().data_dir();
+        let dir = get_data_root();
         fs::create_dir_all(dir).unwrap();
         dir
     })
 }
 
+#[cfg(not(test))]
+fn get_data_root() -> &'static Path {
+    get_project_dir().data_dir()
+}
+
+/// Tests never touch the user's data, on any platform
+#[cfg(test)]
+fn get_data_root() -> &'static Path {
+    super::testing::get_data_dir()
+}
+
 pub fn get_profiles_dir() -> &'static PathBuf {
     static INSTANCE: OnceLock<PathBuf> = OnceLock::new();
     INSTANCE.get_or_init(|| {
@@ -0,0 +1,58 @@
+//! Shared setup for tests that touch the global singletons
+
+use std::{
+    path::Path,
+    sync::{Mutex as StdMutex, OnceLock},
+};
+
+use tempfile::TempDir;
+use tokio::sync::{
+    mpsc::{self, UnboundedReceiver},
+    Mutex, MutexGuard,
+};
+
+use crate::{
+    config::{profile::ProfileManager, tui::TuiConfig},
+    event::Event,
+    task::TaskManager,
+    utils::logger::Logger,
+};
+
+/// Private directory that replaces the user's data directory in tests
+pub fn get_data_dir() -> &'static Path {
+    static INSTANCE: OnceLock<TempDir> = OnceLock::new();
+    INSTANCE.get_or_init(|| TempDir::new().unwrap()).path()
+}
+
+/// Serializes tests on the globals and resets them
+pub async fn lock_globals() -> MutexGuard<'static, ()> {
+    static LOCK: Mutex<()> = Mutex::const_new(());
+
+    let guard = LOCK.lock().await;
+
+    // Tasks left over by a previous test are dropped with their results
+    let manager = TaskManager::get_instance();
+    for task in manager.get_tasks() {
+        manager.cancel(task.id);
+    }
+    while task_events().lock().unwrap().try_recv().is_ok() {}
+
+    *TuiConfig::global().lock().unwrap() = TuiConfig::default();
+    ProfileManager::get_all().lock().unwrap().clear();
+    Logger::get_instance()
+        .lock()
+        .unwrap()
+        .set_buffer(Vec::new());
+
+    guard
+}
+
+/// Receives what `TaskManager` would send to the event loop
+pub fn task_events() -> &'static StdMutex<UnboundedReceiver<Event>> {
+    static INSTANCE: OnceLock<StdMutex<UnboundedReceiver<Event>>> = OnceLock::new();
+    INSTANCE.get_or_init(|| {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        TaskManager::get_instance().set_sender(sender);
+        StdMutex::new(receiver)
+    })
+}